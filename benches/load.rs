@@ -0,0 +1,115 @@
+//! Load time and peak memory of opening a large book.
+//!
+//! Run with `cargo bench --bench load`; `NOVELTUI_BENCH_MB` sets the book
+//! size (default 50). Each case runs in a child process so its peak RSS is
+//! its own. Peak RSS is read from `/proc`, so it is only shown on Linux.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Instant;
+
+use encoding_rs::GBK;
+use noveltui::chapter::{self, HeadingOverrides};
+use noveltui::loader::Book;
+
+const CASES: [&str; 3] = ["utf8", "gbk", "owned-lines"];
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if let Some(i) = args.iter().position(|a| a == "--case") {
+        run_case(&args[i + 1], Path::new(&args[i + 2]));
+        return;
+    }
+
+    let mb: usize = env::var("NOVELTUI_BENCH_MB")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(50);
+    let dir = env::temp_dir().join("noveltui-bench");
+    fs::create_dir_all(&dir).unwrap();
+    let utf8 = dir.join("book-utf8.txt");
+    let gbk = dir.join("book-gbk.txt");
+    let text = book_text(mb * 1024 * 1024);
+    fs::write(&utf8, &text).unwrap();
+    fs::write(&gbk, GBK.encode(&text).0).unwrap();
+
+    println!("{} MiB book, {} lines", mb, text.lines().count());
+    println!(
+        "{:<12} {:>10} {:>10} {:>14}",
+        "case", "load", "parse", "peak RSS"
+    );
+    let exe = env::current_exe().unwrap();
+    for case in CASES {
+        let file: &PathBuf = if case == "gbk" { &gbk } else { &utf8 };
+        let out = Command::new(&exe)
+            .args(["--case", case])
+            .arg(file)
+            .output()
+            .unwrap();
+        print!("{}", String::from_utf8_lossy(&out.stdout));
+    }
+    fs::remove_dir_all(&dir).ok();
+}
+
+// a CJK novel of about `size` bytes, 1000 lines to a chapter
+fn book_text(size: usize) -> String {
+    let paragraph = "他走进房间，看了看窗外的雨，又回头望向桌上那封没有拆开的信。";
+    let mut text = String::with_capacity(size + 1024);
+    let mut line = 0;
+    while text.len() < size {
+        if line % 1000 == 0 {
+            text.push_str(&format!(
+                "第{}章 第{}天\n",
+                line / 1000 + 1,
+                line / 1000 + 1
+            ));
+        } else if line % 2 == 0 {
+            text.push('\n');
+        } else {
+            text.push_str(paragraph);
+            text.push('\n');
+        }
+        line += 1;
+    }
+    text
+}
+
+fn run_case(case: &str, path: &Path) {
+    let start = Instant::now();
+    let (load, parse, chapters) = if case == "owned-lines" {
+        // what loading used to do: one `String` per line, and another per
+        // line again in each chapter's content
+        let text = fs::read_to_string(path).unwrap();
+        let lines: Vec<String> = text.lines().map(|s| s.to_string()).collect();
+        let load = start.elapsed();
+        let book = Book::from_string(text);
+        let chapters = chapter::parse_lines(&book, &HeadingOverrides::default());
+        let content: Vec<Vec<String>> = chapters
+            .iter()
+            .map(|ch| lines[ch.lines()].to_vec())
+            .collect();
+        (load, start.elapsed() - load, content.len())
+    } else {
+        let book = Book::open(path, None).unwrap();
+        let load = start.elapsed();
+        let chapters = chapter::parse_lines(&book, &HeadingOverrides::default()).len();
+        (load, start.elapsed() - load, chapters)
+    };
+    assert!(chapters > 0);
+    println!(
+        "{:<12} {:>8.0}ms {:>8.0}ms {:>14}",
+        case,
+        load.as_secs_f64() * 1000.0,
+        parse.as_secs_f64() * 1000.0,
+        peak_rss().unwrap_or_else(|| "-".to_string())
+    );
+}
+
+fn peak_rss() -> Option<String> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmHWM:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(format!("{:.1} MiB", kb as f64 / 1024.0))
+}
@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::Path;
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::chapter::{self, Chapter};
+use crate::loader::Book;
+use crate::state::BookState;
+
+/// Near-duplicates are chapters whose text is at least this similar.
+const NEAR_DUPLICATE: f64 = 0.8;
+/// Chapters shorter than this (in characters) are too small to compare.
+const MIN_COMPARE_CHARS: usize = 50;
+/// Characters per shingle when comparing chapter text.
+const SHINGLE: usize = 5;
+/// MinHash signature length, split into bands for candidate lookup.
+const HASHES: usize = 16;
+const BANDS: usize = 4;
+/// Gaps in the numbering wider than this are taken for a stray number, such
+/// as a date read as a chapter number, rather than for missing chapters.
+const MAX_GAP: usize = 1000;
+
+/// Something odd about a chapter. Indexes refer to the analysed chapters.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Issue {
+    /// chapter `of` already has this number
+    DuplicateNumber { of: usize },
+    /// same text as chapter `of`
+    ExactDuplicate { of: usize },
+    /// much the same text as chapter `of`
+    NearDuplicate { of: usize, similarity: f64 },
+    /// these numbers appear nowhere in the book, and would come just before this chapter
+    Gap { missing: Range<usize> },
+    /// the number is lower than that of the chapter before, `previous`
+    OutOfOrder { previous: usize },
+}
+
+impl Issue {
+    /// Single-character TOC marker.
+    pub fn marker(&self) -> char {
+        match self {
+            Issue::DuplicateNumber { .. } | Issue::ExactDuplicate { .. } => '=',
+            Issue::NearDuplicate { .. } => '~',
+            Issue::OutOfOrder { .. } => '<',
+            Issue::Gap { .. } => '^',
+        }
+    }
+
+    /// One-line explanation, naming other chapters by title.
+    pub fn describe(&self, chapters: &[Chapter]) -> String {
+        let title = |i: usize| {
+            chapters.get(i).map_or_else(String::new, |c| {
+                format!("\"{}\" (line {})", c.title, c.start_line + 1)
+            })
+        };
+        match self {
+            Issue::DuplicateNumber { of } => format!("same number as {}", title(*of)),
+            Issue::ExactDuplicate { of } => format!("same text as {}", title(*of)),
+            Issue::NearDuplicate { of, similarity } => {
+                format!("{:.0}% similar to {}", similarity * 100.0, title(*of))
+            }
+            Issue::Gap { missing } if missing.len() == 1 => {
+                format!("chapter {} is missing before it", missing.start)
+            }
+            Issue::Gap { missing } => format!(
+                "chapters {}–{} are missing before it",
+                missing.start,
+                missing.end - 1
+            ),
+            Issue::OutOfOrder { previous } => {
+                format!("comes after the higher-numbered {}", title(*previous))
+            }
+        }
+    }
+}
+
+/// Issues found for each chapter, in chapter order.
+pub fn analyze(chapters: &[Chapter], lines: &Book) -> Vec<Vec<Issue>> {
+    let mut issues = vec![Vec::new(); chapters.len()];
+    check_numbers(chapters, &mut issues);
+    check_text(chapters, lines, &mut issues);
+    issues
+}
+
+/// The marker for the most serious issue, if any.
+pub fn marker(issues: &[Issue]) -> Option<char> {
+    issues.iter().map(Issue::marker).min_by_key(|m| match m {
+        '=' => 0,
+        '~' => 1,
+        '<' => 2,
+        _ => 3,
+    })
+}
+
+/// Chapter numbers between the lowest and highest that no heading has,
+/// leaving out gaps too wide to be missing chapters.
+pub fn missing_numbers(chapters: &[Chapter]) -> Vec<Range<usize>> {
+    let present = chapter_numbers(chapters);
+    match (present.first(), present.last()) {
+        (Some(&lo), Some(&hi)) => absent(lo..hi, &present),
+        _ => Vec::new(),
+    }
+}
+
+// the gaps in `range` between the numbers of `present`, which is sorted;
+// only the numbers present are walked, so a stray huge number costs nothing
+fn absent(range: Range<usize>, present: &[usize]) -> Vec<Range<usize>> {
+    let lo = present.partition_point(|&n| n < range.start);
+    let hi = present.partition_point(|&n| n < range.end);
+    let mut out = Vec::new();
+    let mut start = range.start;
+    for &n in present[lo..hi].iter().chain([&range.end]) {
+        if n > start && n - start <= MAX_GAP {
+            out.push(start..n);
+        }
+        start = n.saturating_add(1);
+    }
+    out
+}
+
+// numbers of the chapters proper, leaving out books, parts and unnumbered
+// headings, sorted and without repeats
+fn chapter_numbers(chapters: &[Chapter]) -> Vec<usize> {
+    let mut numbers: Vec<usize> = chapters
+        .iter()
+        .filter(|c| !c.part && c.number > 0)
+        .map(|c| c.number)
+        .collect();
+    numbers.sort_unstable();
+    numbers.dedup();
+    numbers
+}
+
+fn check_numbers(chapters: &[Chapter], issues: &mut [Vec<Issue>]) {
+    let present = chapter_numbers(chapters);
+    let mut first_with: HashMap<usize, usize> = HashMap::new();
+    // highest number so far and its chapter, so one stray heading does not
+    // make every following chapter look out of order or gapped
+    let mut highest: Option<(usize, usize)> = None;
+    for (i, ch) in chapters.iter().enumerate() {
+        // 0 means the heading had no readable number
+        if ch.number == 0 || ch.part {
+            continue;
+        }
+        if let Some(&of) = first_with.get(&ch.number) {
+            issues[i].push(Issue::DuplicateNumber { of });
+            continue;
+        }
+        first_with.insert(ch.number, i);
+        match highest {
+            Some((n, at)) if ch.number < n => {
+                issues[i].push(Issue::OutOfOrder { previous: at });
+            }
+            // a jump this far is a stray number, not the next chapter
+            Some((n, _)) if ch.number - n > MAX_GAP => {}
+            Some((n, _)) => {
+                for missing in absent(n + 1..ch.number, &present) {
+                    issues[i].push(Issue::Gap { missing });
+                }
+                highest = Some((ch.number, i));
+            }
+            None => highest = Some((ch.number, i)),
+        }
+    }
+}
+
+fn check_text(chapters: &[Chapter], lines: &Book, issues: &mut [Vec<Issue>]) {
+    let bodies: Vec<String> = chapters.iter().map(|ch| body(ch, lines)).collect();
+    let mut first_with: HashMap<u64, usize> = HashMap::new();
+    let mut exact = vec![false; chapters.len()];
+    for (i, text) in bodies.iter().enumerate() {
+        if text.is_empty() {
+            continue;
+        }
+        let hash = fnv1a(text.as_bytes());
+        match first_with.get(&hash) {
+            Some(&of) if bodies[of] == *text => {
+                issues[i].push(Issue::ExactDuplicate { of });
+                exact[i] = true;
+            }
+            _ => {
+                first_with.entry(hash).or_insert(i);
+            }
+        }
+    }
+
+    // MinHash signatures, bucketed by band to find candidate pairs cheaply
+    let signatures: Vec<Option<[u64; HASHES]>> = bodies.iter().map(|b| signature(b)).collect();
+    let rows = HASHES / BANDS;
+    let mut buckets: HashMap<(usize, u64), Vec<usize>> = HashMap::new();
+    for (i, sig) in signatures.iter().enumerate() {
+        let Some(sig) = sig else {
+            continue;
+        };
+        let mut best: Option<(usize, f64)> = None;
+        for band in 0..BANDS {
+            let key = (band, fnv1a_words(&sig[band * rows..(band + 1) * rows]));
+            let bucket = buckets.entry(key).or_default();
+            for &j in bucket.iter() {
+                let Some(other) = &signatures[j] else {
+                    continue;
+                };
+                let same = sig.iter().zip(other).filter(|(a, b)| a == b).count();
+                let similarity = same as f64 / HASHES as f64;
+                if similarity >= NEAR_DUPLICATE && best.is_none_or(|(_, s)| similarity > s) {
+                    best = Some((j, similarity));
+                }
+            }
+            bucket.push(i);
+        }
+        if !exact[i]
+            && let Some((of, similarity)) = best
+        {
+            issues[i].push(Issue::NearDuplicate { of, similarity });
+        }
+    }
+}
+
+// chapter text without the heading and whitespace, for comparing
+fn body(ch: &Chapter, lines: &Book) -> String {
+    lines
+        .range(ch.start_line + 1..ch.end_line)
+        .flat_map(|l| l.chars())
+        .filter(|c| !c.is_whitespace())
+        .collect()
+}
+
+fn signature(text: &str) -> Option<[u64; HASHES]> {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() < MIN_COMPARE_CHARS {
+        return None;
+    }
+    let mut sig = [u64::MAX; HASHES];
+    let mut buf = [0u8; SHINGLE * 4];
+    for shingle in chars.windows(SHINGLE) {
+        let mut len = 0;
+        for c in shingle {
+            len += c.encode_utf8(&mut buf[len..]).len();
+        }
+        // k hashes from two (Kirsch–Mitzenmacher)
+        let h1 = fnv1a(&buf[..len]);
+        let h2 = h1.rotate_left(32).wrapping_mul(0x9e3779b97f4a7c15) | 1;
+        for (k, slot) in sig.iter_mut().enumerate() {
+            let h = h1.wrapping_add((k as u64).wrapping_mul(h2));
+            *slot = (*slot).min(h);
+        }
+    }
+    Some(sig)
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn fnv1a_words(words: &[u64]) -> u64 {
+    let bytes: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+    fnv1a(&bytes)
+}
+
+/// `noveltui check`: list the chapter problems found in a book.
+pub fn check(path: &Path) -> Result<()> {
+    let state = BookState::load(path);
+    let lines = Book::open(path, state.encoding())?;
+    let chapters = chapter::parse_lines(&lines, &state.headings);
+    let issues = analyze(&chapters, &lines);
+    println!("{}: {} chapters", path.display(), chapters.len());
+    let mut count = 0;
+    for (ch, found) in chapters.iter().zip(&issues) {
+        for issue in found {
+            count += 1;
+            println!(
+                "  line {:>7}  {}  {}",
+                ch.start_line + 1,
+                ch.title,
+                issue.describe(&chapters)
+            );
+        }
+    }
+    let missing: usize = missing_numbers(&chapters).iter().map(|r| r.len()).sum();
+    let kind = |f: fn(&Issue) -> bool| issues.iter().flatten().filter(|i| f(i)).count();
+    println!(
+        "{} issues: {} missing numbers, {} repeated numbers, {} exact and {} near duplicates, {} out of order",
+        count,
+        missing,
+        kind(|i| matches!(i, Issue::DuplicateNumber { .. })),
+        kind(|i| matches!(i, Issue::ExactDuplicate { .. })),
+        kind(|i| matches!(i, Issue::NearDuplicate { .. })),
+        kind(|i| matches!(i, Issue::OutOfOrder { .. })),
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::Counts;
+
+    fn numbered(numbers: &[usize]) -> Vec<Chapter> {
+        numbers
+            .iter()
+            .enumerate()
+            .map(|(i, &number)| Chapter {
+                number,
+                title: format!("第{}章", number),
+                part: false,
+                start_line: i,
+                end_line: i + 1,
+                counts: Counts::default(),
+            })
+            .collect()
+    }
+
+    fn numbering_issues(numbers: &[usize]) -> Vec<Vec<Issue>> {
+        let chapters = numbered(numbers);
+        let mut issues = vec![Vec::new(); chapters.len()];
+        check_numbers(&chapters, &mut issues);
+        issues
+    }
+
+    #[test]
+    fn missing_numbers_are_gaps_between_neighbours() {
+        let chapters = numbered(&[1, 2, 5, 4, 9, 9]);
+        assert_eq!(missing_numbers(&chapters), [3..4, 6..9]);
+        assert!(missing_numbers(&numbered(&[])).is_empty());
+        assert!(missing_numbers(&numbered(&[0, 0])).is_empty());
+    }
+
+    #[test]
+    fn stray_huge_number_is_not_a_gap() {
+        let chapters = numbered(&[1, 2, 4, 20240101, 5, usize::MAX]);
+        assert_eq!(missing_numbers(&chapters), vec![Range { start: 3, end: 4 }]);
+        let issues = numbering_issues(&[1, 2, 4, 20240101, 5, usize::MAX, 6]);
+        assert_eq!(issues[2], [Issue::Gap { missing: 3..4 }]);
+        // the stray numbers are passed over, so what follows is in order
+        assert!(issues[3..].iter().all(Vec::is_empty));
+    }
+
+    #[test]
+    fn gap_leaves_out_numbers_found_elsewhere() {
+        let issues = numbering_issues(&[1, 5, 3]);
+        assert_eq!(
+            issues[1],
+            [Issue::Gap { missing: 2..3 }, Issue::Gap { missing: 4..5 }]
+        );
+        assert_eq!(issues[2], [Issue::OutOfOrder { previous: 1 }]);
+    }
+}
@@ -0,0 +1,79 @@
+use std::ops::Range;
+
+use ratatui::style::{Color, Modifier, Style};
+use serde::{Deserialize, Serialize};
+
+use crate::text;
+
+/// Highlight colors offered when annotating, all safe on 16-color terminals.
+pub const COLORS: &[(&str, Color)] = &[
+    ("yellow", Color::LightYellow),
+    ("green", Color::LightGreen),
+    ("cyan", Color::LightCyan),
+    ("magenta", Color::LightMagenta),
+    ("red", Color::LightRed),
+];
+
+/// A point in the book: global line number and char offset in that line.
+/// Global lines survive chapter re-detection, unlike chapter-relative ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TextPos {
+    pub line: usize,
+    pub char: usize,
+}
+
+/// A highlighted range of text with an optional note.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Annotation {
+    pub start: TextPos,
+    /// exclusive
+    pub end: TextPos,
+    /// one of the names in `COLORS`
+    pub color: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub note: String,
+    /// the highlighted text when it was made, for listing and export
+    pub quote: String,
+    /// seconds since the Unix epoch
+    pub created: u64,
+}
+
+impl Annotation {
+    pub fn style(&self) -> Style {
+        let color = COLORS
+            .iter()
+            .find(|(name, _)| *name == self.color)
+            .map_or(Color::LightYellow, |(_, c)| *c);
+        let style = Style::default().fg(Color::Black).bg(color);
+        if self.note.is_empty() {
+            style
+        } else {
+            // underline marks highlights that carry a note
+            style.add_modifier(Modifier::UNDERLINED)
+        }
+    }
+
+    /// Byte range this annotation covers in global line `line`, if any.
+    pub fn range_on_line(&self, line: usize, text: &str) -> Option<Range<usize>> {
+        if line < self.start.line || line > self.end.line {
+            return None;
+        }
+        let start = if line == self.start.line {
+            text::char_to_byte(text, self.start.char)
+        } else {
+            0
+        };
+        let end = if line == self.end.line {
+            text::char_to_byte(text, self.end.char)
+        } else {
+            text.len()
+        };
+        (end > start).then_some(start..end)
+    }
+}
+
+/// Name of the color after `name` in `COLORS`, wrapping around.
+pub fn next_color(name: &str) -> &'static str {
+    let i = COLORS.iter().position(|(n, _)| *n == name).unwrap_or(0);
+    COLORS[(i + 1) % COLORS.len()].0
+}
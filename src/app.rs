@@ -1,21 +1,44 @@
 use std::fs;
+use std::ops::Range;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
 
+use crate::analysis::{self, Issue};
+use crate::annotation::{self, Annotation, TextPos};
 use crate::args::Options;
+use crate::autoscroll::{self, AutoScroll, AutoScrollConfig};
+use crate::charset::Charset;
+use crate::config::{Config, LayoutConfig, Side};
+use crate::dict::{self, Dictionaries};
+use crate::event::{AppEvent, EventLoop};
+use crate::goto;
+use crate::loader::{self, Book, Trial};
+use crate::loading;
+use crate::notes;
+use crate::state::{self, BookState, BookmarkInfo, ReadingPosition, UiState, VocabEntry};
+use crate::stats::{self, LineCounts, Tracker};
+use crate::text;
+use crate::theme::{self, ColorDepth, Theme};
+use crate::tts::{self, Tts, TtsConfig};
 use color_eyre::Result;
-use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use color_eyre::eyre::eyre;
+use crossterm::event::{
+    Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
 use ratatui::{
     DefaultTerminal, Frame,
-    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    layout::{Alignment, Constraint, Direction, Layout, Position, Rect},
     style::{Color, Modifier, Style},
-    text::Text,
+    text::{Line, Span, Text},
     widgets::*,
 };
 
-use crate::bookmark::{self, BOOKMARK_SYMBOL, Bookmark};
-use crate::chapter::{self, Chapter};
-use chardetng::EncodingDetector;
+use crate::bookmark::{self, BOOKMARK_SYMBOL, Bookmark, BookmarkSort};
+use crate::chapter::Chapter;
 use textwrap;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
 pub enum Focus {
@@ -23,6 +46,74 @@ pub enum Focus {
     Toc,
     Content,
     Bookmark,
+    Annotation,
+}
+
+// word picker over the current line and the definitions popup
+#[derive(Debug, Default)]
+struct Lookup {
+    // byte ranges of the words that can be picked
+    targets: Vec<Range<usize>>,
+    selected: usize,
+    // looked-up word with (dictionary, definition) pairs, while the popup is open
+    popup: Option<(String, Vec<(String, String)>)>,
+    scroll: u16,
+}
+
+// text being selected for a new annotation; lines index into view_lines
+#[derive(Debug)]
+struct Selection {
+    anchor_line: usize,
+    // byte range of the word the selection started on
+    anchor: Range<usize>,
+    // words on the cursor line (the selected content line)
+    targets: Vec<Range<usize>>,
+    cursor: usize,
+}
+
+// a starting point given on the command line
+#[derive(Debug)]
+enum StartJump {
+    // 1-based position in the TOC
+    Chapter(usize),
+    // 1-based position in the bookmark menu
+    Bookmark(usize),
+    Target(goto::Target),
+}
+
+// the `:` go-to prompt
+#[derive(Debug, Default)]
+struct Prompt {
+    input: String,
+    // position while browsing history with ↑/↓, and the input typed before it
+    history_index: Option<usize>,
+    draft: String,
+    // chapter titles matching the input when Tab was first pressed
+    completions: Vec<String>,
+    completion: Option<usize>,
+}
+
+// the note and color popup for one annotation
+#[derive(Debug)]
+struct AnnotationEdit {
+    index: usize,
+    note: String,
+    color: &'static str,
+}
+
+// the list of encodings to reopen the book in
+#[derive(Debug)]
+struct EncodingPicker {
+    trials: Vec<Trial>,
+    // row 0 is detecting the encoding, then one row per trial
+    state: ListState,
+}
+
+// what the book overview shows that is costly to find, worked out when it opens
+#[derive(Debug)]
+struct Overview {
+    file_size: u64,
+    gaps: Vec<Range<usize>>,
 }
 
 #[derive(Debug, Default)]
@@ -31,16 +122,16 @@ pub struct App {
     running: bool,
     // file path
     file_path: PathBuf,
-    // full file content
-    lines: Vec<String>,
+    // full file content; shared with the loading worker until it is done
+    lines: Arc<Book>,
     // offset is now relative to view_lines
     view_offset: usize,
     // TOC
     chapters: Vec<Chapter>,
     // TOC selection state
     toc_state: ListState,
-    // current view (either selected chapter or whole file)
-    view_lines: Vec<String>,
+    // global lines of the current view (either selected chapter or whole file)
+    view: Range<usize>,
     // new: content selection state + focus
     content_state: ListState,
     // current focus
@@ -53,174 +144,1340 @@ pub struct App {
     show_bookmark_menu: bool,
     // whether to show title and footer
     show_title_footer: bool,
-    // initial jump targets
-    initial_bookmark_jump: Option<usize>,
-    // new: initial chapter jump target
-    initial_chapter_jump: Option<usize>,
+    // where to start instead of the saved position
+    initial_jump: Option<StartJump>,
+    // line browsing a side pane started from, and the line it has reached
+    browsing: Option<(usize, usize)>,
+    // built-in and user themes
+    themes: Vec<Theme>,
+    // index of the active theme in `themes`
+    theme_index: usize,
+    // side pane widths and placement
+    layout: LayoutConfig,
+    // state remembered between sessions
+    ui_state: UiState,
+    // where each visible pane was drawn last frame, for mouse hit-testing
+    pane_areas: Vec<(Focus, Rect)>,
+    // width content lines were wrapped to last frame
+    content_wrap_width: usize,
+    // side pane whose border is being dragged
+    dragging: Option<Focus>,
+    // per-book data remembered between sessions
+    book_state: BookState,
+    // message shown in the footer until it expires
+    status: Option<(String, Instant)>,
+    // terminal was resized; keep the selected line in view without scrolling
+    keep_anchor: bool,
+    // speed and unit auto-scroll starts with
+    autoscroll_config: AutoScrollConfig,
+    // running auto-scroll, if any
+    autoscroll: Option<AutoScroll>,
+    // engine used for reading aloud
+    tts_config: TtsConfig,
+    // running read-aloud session, if any
+    tts: Option<Tts>,
+    // dictionary files from the config
+    dictionary_paths: Vec<PathBuf>,
+    // dictionaries, opened on first lookup
+    dictionaries: Option<Dictionaries>,
+    // active word lookup, if any
+    lookup: Option<Lookup>,
+    // whether to show the annotations pane
+    show_annotations: bool,
+    // annotations pane selection state
+    annotation_state: ListState,
+    // text selection for a new annotation, if any
+    selection: Option<Selection>,
+    // open annotation editor, if any
+    editing: Option<AnnotationEdit>,
+    // label being typed for the selected bookmark, if any
+    renaming: Option<String>,
+    // whether the selected bookmark's context popup is open
+    previewing: bool,
+    // open go-to prompt, if any
+    prompt: Option<Prompt>,
+    // the reading session, from the first frame on
+    tracker: Option<Tracker>,
+    // per-line text counts for time estimates
+    line_counts: LineCounts,
+    // whether the stats popup is open
+    show_stats: bool,
+    // open book overview popup, if any
+    overview: Option<Overview>,
+    // problems found in each chapter, indexed like `chapters`
+    chapter_issues: Vec<Vec<Issue>>,
+    // leave chapters that repeat an earlier one word for word out of the TOC
+    hide_duplicate_chapters: bool,
+    // the worker loading the book, until it is done
+    loading: Option<Loading>,
+    // encoding to read the book in; from --encoding, or picked and saved
+    encoding: Option<Charset>,
+    // open encoding picker, if any
+    encoding_picker: Option<EncodingPicker>,
 }
 
+// progress of the background load
+#[derive(Debug)]
+struct Loading {
+    updates: Receiver<loading::Update>,
+    // bytes decoded and file size
+    decoded: (u64, u64),
+    // the reader moved before loading finished, so stay where they are
+    touched: bool,
+}
+
+// how many `:` commands are remembered
+const GOTO_HISTORY_LEN: usize = 50;
+
+// how long a status message stays in the footer
+const STATUS_TIMEOUT: Duration = Duration::from_secs(3);
+
 impl App {
-    pub fn new(args: Options) -> Self {
-        // Change signature to accept Options
+    pub fn new(args: Options, config: Config) -> Result<Self> {
+        let depth = config.color_depth.unwrap_or_else(ColorDepth::detect);
+        let themes = theme::load_themes(&config.themes, depth)?;
+        let theme_index = match &config.theme {
+            Some(name) => themes
+                .iter()
+                .position(|t| t.name == *name)
+                .ok_or_else(|| eyre!("Unknown theme \"{}\"", name))?,
+            None => 0,
+        };
+        let initial_jump = if let Some(n) = args.chapter {
+            Some(StartJump::Chapter(n))
+        } else if let Some(n) = args.bookmark {
+            Some(StartJump::Bookmark(n))
+        } else if let Some(n) = args.chapter_number {
+            Some(StartJump::Target(goto::Target::Chapter(n)))
+        } else if let Some(line) = args.line {
+            Some(StartJump::Target(goto::Target::Line(line)))
+        } else {
+            args.percent
+                .map(|p| StartJump::Target(goto::Target::Percent(p)))
+        };
+        let ui_state = UiState::load();
+        let layout = ui_state.layout.unwrap_or(config.layout).clamped();
         let mut toc_state = ListState::default();
         toc_state.select(Some(0));
         let mut content_state = ListState::default();
         content_state.select(Some(0));
-        Self {
+        Ok(Self {
             running: false,
-            file_path: args.file_path,
-            lines: Vec::new(),
+            // clap requires FILE unless a subcommand was given
+            file_path: args.file_path.unwrap_or_default(),
+            lines: Arc::default(),
             view_offset: 0,
             chapters: Vec::new(),
             toc_state,
-            view_lines: Vec::new(),
+            view: 0..0,
             content_state,
             focus: Focus::Toc,
             bookmarks: Vec::new(),
             bookmark_state: ListState::default(),
             show_bookmark_menu: false,
             show_title_footer: true,
-            initial_bookmark_jump: args.bookmark, // Store the bookmark index
-            initial_chapter_jump: args.chapter,   // Store the chapter index
-        }
+            initial_jump,
+            browsing: None,
+            themes,
+            theme_index,
+            layout,
+            ui_state,
+            pane_areas: Vec::new(),
+            content_wrap_width: 1,
+            dragging: None,
+            book_state: BookState::default(),
+            status: None,
+            keep_anchor: false,
+            autoscroll_config: config.autoscroll,
+            autoscroll: None,
+            tts_config: config.tts,
+            tts: None,
+            dictionary_paths: config.dictionaries,
+            dictionaries: None,
+            lookup: None,
+            show_annotations: false,
+            annotation_state: ListState::default(),
+            selection: None,
+            editing: None,
+            renaming: None,
+            previewing: false,
+            prompt: None,
+            tracker: None,
+            line_counts: LineCounts::default(),
+            show_stats: false,
+            overview: None,
+            chapter_issues: Vec::new(),
+            hide_duplicate_chapters: config.hide_duplicate_chapters,
+            loading: None,
+            encoding: args.encoding,
+            encoding_picker: None,
+        })
+    }
+
+    fn theme(&self) -> &Theme {
+        &self.themes[self.theme_index]
+    }
+
+    fn cycle_theme(&mut self) {
+        self.theme_index = (self.theme_index + 1) % self.themes.len();
+        self.set_status(format!("Theme: {}", self.theme().name));
+    }
+
+    fn set_status(&mut self, message: impl Into<String>) {
+        self.status = Some((message.into(), Instant::now()));
     }
 
+    // start loading the book on a worker thread; see `poll_loading`
     fn load_file(&mut self) -> Result<()> {
-        // Try reading as UTF-8 first
-        let content = match fs::read_to_string(&self.file_path) {
-            Ok(s) => s,
-            Err(_) => {
-                // Fallback: read bytes and auto-detect encoding then decode
-                let bytes = fs::read(&self.file_path)?;
-                Self::decode_with_auto_detect(&bytes)
-            }
-        };
-        self.lines = content.lines().map(|s| s.to_string()).collect();
-        // parse chapters from lines
-        self.chapters = chapter::parse_lines(&self.lines);
-        self.bookmarks = bookmark::parse_bookmarks(&self.chapters);
-        if !self.bookmarks.is_empty() {
-            self.bookmark_state.select(Some(0));
+        self.book_state = BookState::load(&self.file_path);
+        self.book_state.path = Some(fs::canonicalize(&self.file_path)?);
+        // --encoding wins over one picked before
+        self.encoding = self.encoding.or(self.book_state.encoding());
+        self.loading = Some(Loading {
+            updates: loading::spawn(
+                self.file_path.clone(),
+                self.encoding,
+                self.book_state.headings.clone(),
+                self.hide_duplicate_chapters,
+            ),
+            decoded: (0, 0),
+            touched: false,
+        });
+        Ok(())
+    }
+
+    // take in what the loading worker sent since the last frame
+    fn poll_loading(&mut self) -> Result<()> {
+        let Some(loading) = self.loading.as_mut() else {
+            return Ok(());
+        };
+        let updates: Vec<loading::Update> = loading.updates.try_iter().collect();
+        for update in updates {
+            match update {
+                loading::Update::Decoded { done, total } => {
+                    if let Some(loading) = self.loading.as_mut() {
+                        loading.decoded = (done, total);
+                    }
+                }
+                loading::Update::Text(book) => self.lines = book,
+                loading::Update::Chapters(found) => {
+                    let first = self.chapters.is_empty();
+                    self.chapter_issues.extend(found.iter().map(|_| Vec::new()));
+                    self.chapters.extend(found);
+                    // show the saved position, or else the first chapter,
+                    // while the rest are found
+                    let touched = self.loading.as_ref().is_some_and(|l| l.touched);
+                    if touched || self.initial_jump.is_some() {
+                        continue;
+                    }
+                    let saved = self
+                        .book_state
+                        .position
+                        .filter(|p| p.chapter < self.chapters.len());
+                    match saved {
+                        Some(pos)
+                            if self.toc_state.selected().is_some()
+                                && self.current_position() == pos => {}
+                        Some(pos) => self.go_to_position(pos),
+                        None if first => self.select_chapter(0),
+                        None => {}
+                    }
+                }
+                loading::Update::Done(result) => self.finish_loading(result?)?,
+            }
         }
-        // set initial view: first chapter if exists, else whole file
-        if !self.chapters.is_empty() {
-            self.toc_state.select(Some(0));
-            self.select_chapter(0);
+        Ok(())
+    }
+
+    fn finish_loading(&mut self, loaded: loading::Loaded) -> Result<()> {
+        let touched = self.loading.take().is_some_and(|l| l.touched);
+        let line = self.global_line(self.content_state.selected().unwrap_or(0));
+        self.chapters = loaded.chapters;
+        self.chapter_issues = loaded.issues;
+        self.line_counts = loaded.line_counts;
+        self.show_bookmarks(&loaded.marked);
+        if self.overview.is_some() {
+            // opened while loading, before all the chapters were in
+            self.open_overview();
+        }
+        if touched {
+            if self.chapters.is_empty() {
+                self.show_whole_file();
+            }
+            self.go_to_global_line(line);
         } else {
-            self.toc_state.select(None);
-            self.view_lines = self.lines.clone();
-            self.view_offset = 0;
-            if !self.view_lines.is_empty() {
-                self.content_state.select(Some(0));
+            // set initial view: first chapter if exists, else whole file
+            if !self.chapters.is_empty() {
+                self.toc_state.select(Some(0));
+                self.select_chapter(0);
             } else {
-                self.content_state.select(None);
+                self.show_whole_file();
             }
+            self.handle_initial_jumps()?;
+        }
+        self.tracker = Some(Tracker::new(
+            self.global_line(self.content_state.selected().unwrap_or(0)),
+            self.toc_state.selected().unwrap_or(0),
+        ));
+        if self.lines.replaced() > 0 {
+            self.set_status(format!(
+                "{} characters could not be read as {}; press C to try another encoding",
+                self.lines.replaced(),
+                self.lines.encoding().name()
+            ));
         }
         Ok(())
     }
 
-    // helper
-    fn decode_with_auto_detect(bytes: &[u8]) -> String {
-        let mut det = EncodingDetector::new();
-        det.feed(bytes, true);
-        let encoding = det.guess(None, true);
-        let (cow, _, _) = encoding.decode(bytes);
-        cow.into_owned()
+    // whether the book is still loading, telling the reader so if it is
+    fn still_loading(&mut self) -> bool {
+        if self.loading.is_some() {
+            self.set_status("Still loading the book; try again in a moment");
+        }
+        self.loading.is_some()
+    }
+
+    // parse chapters from lines, with the reader's heading overrides
+    fn parse_chapters(&mut self) {
+        (self.chapters, self.chapter_issues) = loading::parse(
+            &self.lines,
+            &self.book_state.headings,
+            self.hide_duplicate_chapters,
+            |_| {},
+        );
+        self.refresh_bookmarks();
+    }
+
+    fn show_whole_file(&mut self) {
+        self.toc_state.select(None);
+        self.view = 0..self.lines.len();
+        self.view_offset = 0;
+        if !self.view.is_empty() {
+            self.content_state.select(Some(0));
+        } else {
+            self.content_state.select(None);
+        }
+    }
+
+    // demote the selected chapter's heading (TOC), or make the current line a
+    // heading or body text (content); kept as a per-book override
+    fn toggle_heading(&mut self) {
+        if self.still_loading() {
+            return;
+        }
+        let line = match self.focus {
+            Focus::Toc => match self.toc_state.selected().and_then(|c| self.chapters.get(c)) {
+                Some(ch) => ch.start_line,
+                None => return,
+            },
+            Focus::Content => match self.content_state.selected() {
+                Some(idx) => self.global_line(idx),
+                None => return,
+            },
+            _ => return,
+        };
+        let Some(text) = self.lines.get(line) else {
+            return;
+        };
+        let is_heading = self.chapters.iter().any(|c| c.start_line == line);
+        if !is_heading && text.trim().is_empty() {
+            return;
+        }
+        let title = text.trim().to_string();
+        self.book_state.headings.toggle(line, is_heading);
+        self.parse_chapters();
+        if self.chapters.is_empty() {
+            self.show_whole_file();
+            self.content_state.select(Some(line));
+        } else {
+            self.go_to_global_line(line);
+        }
+        self.set_status(if is_heading {
+            format!("No longer a heading: {}", title)
+        } else {
+            format!("Now a heading: {}", title)
+        });
+        if let Err(e) = self.book_state.save(&self.file_path) {
+            self.set_status(format!("Cannot save heading change: {}", e));
+        }
     }
 
     pub fn run(&mut self, mut terminal: DefaultTerminal) -> Result<()> {
         self.load_file()?;
         self.running = true;
 
-        // Call the new method to handle initial jumps
-        self.handle_initial_jumps()?; // New line: call the extracted logic
-
+        let mut events = EventLoop::default();
         while self.running {
+            self.poll_loading()?;
             terminal.draw(|f| {
                 self.render(f);
             })?;
-            self.handle_event(event::read()?);
-            //self.handle_crossterm_event();
+            match events.next_event()? {
+                AppEvent::Terminal(event) => self.handle_event(event),
+                AppEvent::Tick => self.on_tick(),
+            }
         }
-        Ok(())
+        self.save_position()?;
+        self.save_ui_state()
+    }
+
+    fn on_tick(&mut self) {
+        if let Some((_, since)) = &self.status
+            && since.elapsed() >= STATUS_TIMEOUT
+        {
+            self.status = None;
+        }
+        self.advance_autoscroll();
+        self.advance_tts();
+        let hands_free = self.autoscroll.as_ref().is_some_and(|a| !a.paused)
+            || self.tts.as_ref().is_some_and(|t| !t.paused);
+        if let Some(tracker) = self.tracker.as_mut() {
+            // being read to or scrolled for is reading too
+            if hands_free {
+                tracker.activity();
+            }
+            tracker.tick();
+        }
+        self.track_reading();
+    }
+
+    // count what was read since the last event
+    fn track_reading(&mut self) {
+        let line = self.global_line(self.content_state.selected().unwrap_or(0));
+        let chapter = self.toc_state.selected().unwrap_or(0);
+        if let Some(tracker) = self.tracker.as_mut() {
+            tracker.moved(line, chapter, &self.lines, &self.line_counts);
+        }
+    }
+
+    // forget the lines skipped by a jump
+    fn track_jump(&mut self) {
+        let line = self.current_line();
+        let chapter = self.toc_state.selected().unwrap_or(0);
+        if let Some(tracker) = self.tracker.as_mut() {
+            tracker.jumped(line, chapter);
+        }
+    }
+
+    // keep the current session with the book, replacing its last saved copy
+    fn commit_session(&mut self) {
+        let Some(tracker) = self.tracker.as_ref().filter(|t| t.worth_keeping()) else {
+            return;
+        };
+        let session = tracker.session.clone();
+        match self.book_state.sessions.last_mut() {
+            Some(last) if last.start == session.start => *last = session,
+            _ => self.book_state.sessions.push(session),
+        }
+    }
+
+    // CJK characters plus words per second over every session of this book
+    fn reading_speed(&self) -> Option<f64> {
+        let sessions = &self.book_state.sessions;
+        let current = self
+            .tracker
+            .as_ref()
+            .map(|t| &t.session)
+            .filter(|s| sessions.last().is_none_or(|last| last.start != s.start));
+        stats::speed(sessions.iter().chain(current))
+    }
+
+    // time left in the chapter and in the book at the current reading speed
+    fn time_left(&self) -> Option<(Duration, Duration)> {
+        let speed = self.reading_speed()?;
+        let line = self.global_line(self.content_state.selected().unwrap_or(0));
+        let chapter_end = match self.toc_state.selected().and_then(|c| self.chapters.get(c)) {
+            Some(ch) => ch.end_line,
+            None => self.lines.len(),
+        };
+        let chapter = self.line_counts.range(&self.lines, line..chapter_end);
+        let book = self.line_counts.range(&self.lines, line..self.lines.len());
+        Some((
+            stats::time_to_read(chapter, speed),
+            stats::time_to_read(book, speed),
+        ))
+    }
+
+    fn toggle_toc_counts(&mut self) {
+        self.layout.toc_counts = !self.layout.toc_counts;
+        self.ui_state.layout = Some(self.layout);
+    }
+
+    // progress of the load, until there is a chapter to show
+    fn render_loading(&self, frame: &mut Frame) {
+        let Some(loading) = &self.loading else {
+            return;
+        };
+        let mib = |bytes: u64| bytes as f64 / 1048576.0;
+        let (done, total) = loading.decoded;
+        let progress = if !self.lines.is_empty() {
+            format!("{} lines read, finding chapters…", self.lines.len())
+        } else if total > 0 {
+            format!(
+                "{:.1} of {:.1} MiB decoded ({:.0}%)",
+                mib(done),
+                mib(total),
+                done as f64 * 100.0 / total as f64
+            )
+        } else {
+            "Reading…".to_string()
+        };
+        let area = centered_rect(50, 20, frame.area());
+        let popup = Paragraph::new(vec![Line::from(""), Line::from(progress)])
+            .alignment(Alignment::Center)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .border_style(self.theme().border)
+                    .title(" Loading "),
+            )
+            .style(self.theme().base);
+        frame.render_widget(Clear, area);
+        frame.render_widget(popup, area);
+    }
+
+    fn render_encoding_picker(&mut self, frame: &mut Frame) {
+        let base = self.theme().base;
+        let border = self.theme().border;
+        let dim = self.theme().footer_info;
+        let highlight = self.theme().toc_highlight;
+        let Some(picker) = self.encoding_picker.as_mut() else {
+            return;
+        };
+        let detected = picker.trials.iter().find(|t| t.detected);
+        let mut items = vec![ListItem::new(format!(
+            "Detect automatically ({})",
+            detected.map_or("UTF-8", |t| t.encoding.name())
+        ))];
+        items.extend(picker.trials.iter().map(|t| {
+            let errors = match t.errors {
+                0 => String::new(),
+                1 => "1 error".to_string(),
+                n => format!("{} errors", n),
+            };
+            ListItem::new(Line::from(vec![
+                Span::raw(format!(
+                    "{:<13}{:<10}{:<12}",
+                    t.encoding.name(),
+                    if t.detected { "detected" } else { "" },
+                    errors
+                )),
+                Span::styled(t.preview.clone(), dim),
+            ]))
+        }));
+        let area = centered_rect(70, 50, frame.area());
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .border_style(border)
+                    .title(" Reopen with encoding ")
+                    .title_bottom(" [j/k]Move [Enter]Reopen [Esc]Close "),
+            )
+            .style(base)
+            .highlight_style(highlight)
+            .highlight_symbol("> ");
+        frame.render_widget(Clear, area);
+        frame.render_stateful_widget(list, area, &mut picker.state);
+    }
+
+    fn open_overview(&mut self) {
+        self.overview = Some(Overview {
+            file_size: fs::metadata(&self.file_path).map_or(0, |m| m.len()),
+            gaps: analysis::missing_numbers(&self.chapters),
+        });
+    }
+
+    fn render_overview(&self, frame: &mut Frame, overview: &Overview) {
+        let total = self.line_counts.range(&self.lines, 0..self.lines.len());
+        let size = overview.file_size;
+        let mut rows = vec![
+            (
+                "File",
+                if size < 1 << 20 {
+                    format!(
+                        "{} ({:.1} KiB)",
+                        self.file_path.display(),
+                        size as f64 / 1024.0
+                    )
+                } else {
+                    format!(
+                        "{} ({:.1} MiB)",
+                        self.file_path.display(),
+                        size as f64 / 1048576.0
+                    )
+                },
+            ),
+            (
+                "Text",
+                format!(
+                    "{} lines · {} chars · {} CJK · {} words",
+                    self.lines.len(),
+                    total.chars,
+                    total.cjk,
+                    total.words
+                ),
+            ),
+            (
+                "Encoding",
+                format!(
+                    "{} ({}{})",
+                    self.lines.encoding().name(),
+                    if self.encoding.is_some() {
+                        "picked"
+                    } else {
+                        "detected"
+                    },
+                    match self.lines.replaced() {
+                        0 => String::new(),
+                        n => format!(", {} characters unreadable", n),
+                    }
+                ),
+            ),
+            ("Chapters", self.chapters.len().to_string()),
+        ];
+        if !self.chapters.is_empty() {
+            let units: u64 = self.chapters.iter().map(|c| c.counts.units()).sum();
+            let unit = stats::unit_name(total);
+            rows.push((
+                "Average",
+                format!("{} {}", units / self.chapters.len() as u64, unit),
+            ));
+            if let Some(longest) = self.chapters.iter().max_by_key(|c| c.counts.units()) {
+                rows.push((
+                    "Longest",
+                    format!("{} ({} {})", longest.title, longest.counts.units(), unit),
+                ));
+            }
+            let gaps = &overview.gaps;
+            let missing: usize = gaps.iter().map(|g| g.len()).sum();
+            let listed: Vec<String> = gaps
+                .iter()
+                .take(10)
+                .map(|g| {
+                    if g.len() == 1 {
+                        g.start.to_string()
+                    } else {
+                        format!("{}–{}", g.start, g.end - 1)
+                    }
+                })
+                .collect();
+            rows.push((
+                "Gaps",
+                match gaps.len() {
+                    0 => "none".to_string(),
+                    n if n > listed.len() => {
+                        format!("{} missing: {}, …", missing, listed.join(", "))
+                    }
+                    _ => format!("{} missing: {}", missing, listed.join(", ")),
+                },
+            ));
+            let flagged = self.chapter_issues.iter().filter(|i| !i.is_empty()).count();
+            rows.push((
+                "Flagged",
+                format!(
+                    "{} chapters (marked in the TOC; see `noveltui check`)",
+                    flagged
+                ),
+            ));
+        }
+        let lines: Vec<Line> = rows
+            .into_iter()
+            .map(|(label, value)| {
+                Line::from(vec![
+                    Span::styled(format!("{:<10}", label), self.theme().title),
+                    Span::raw(value),
+                ])
+            })
+            .collect();
+        let area = centered_rect(70, 40, frame.area());
+        let popup = Paragraph::new(Text::from(lines))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .border_style(self.theme().border)
+                    .title(" Book overview "),
+            )
+            .style(self.theme().base)
+            .wrap(Wrap { trim: false });
+        frame.render_widget(Clear, area);
+        frame.render_widget(popup, area);
+    }
+
+    fn render_stats_popup(&self, frame: &mut Frame) {
+        let now = state::now_secs();
+        let current = self.tracker.as_ref().map(|t| &t.session);
+        let mut sessions: Vec<&stats::Session> = self.book_state.sessions.iter().collect();
+        if let Some(current) = current
+            && sessions
+                .last()
+                .is_none_or(|last| last.start != current.start)
+        {
+            sessions.push(current);
+        }
+        let today = stats::daily(&sessions, 1, now)
+            .first()
+            .map(|(_, sum)| *sum)
+            .unwrap_or_default();
+        let book = stats::Summary::of(sessions.iter().copied());
+        let mut rows = vec![
+            (
+                "This session",
+                current.map_or_else(String::new, |s| stats::Summary::of([s]).describe()),
+            ),
+            ("Today", today.describe()),
+            (
+                "This book",
+                format!("{} in {} sessions", book.describe(), book.sessions),
+            ),
+        ];
+        match self.time_left() {
+            Some((chapter, book)) => rows.push((
+                "Time left",
+                format!(
+                    "{} in chapter · {} in book",
+                    stats::format_span(chapter.as_secs()),
+                    stats::format_span(book.as_secs())
+                ),
+            )),
+            None => rows.push(("Time left", "read for a minute to estimate".to_string())),
+        }
+        let lines: Vec<Line> = rows
+            .into_iter()
+            .map(|(label, value)| {
+                Line::from(vec![
+                    Span::styled(format!("{:<14}", label), self.theme().title),
+                    Span::raw(value),
+                ])
+            })
+            .collect();
+        let area = centered_rect(70, 40, frame.area());
+        let popup = Paragraph::new(Text::from(lines))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .border_style(self.theme().border)
+                    .title(" Reading stats "),
+            )
+            .style(self.theme().base)
+            .wrap(Wrap { trim: false });
+        frame.render_widget(Clear, area);
+        frame.render_widget(popup, area);
+    }
+
+    fn toggle_tts(&mut self) {
+        if self.tts.take().is_some() {
+            return;
+        }
+        self.focus = Focus::Content;
+        let at = self.current_position();
+        let tts = Tts::new(self.tts_config.clone(), at, 0..0);
+        self.tts = Some(tts);
+        self.read_from_current_line(false);
+    }
+
+    // speak the first (or last) sentence at or after the cursor, moving down
+    // through blank lines and into later chapters as needed
+    fn read_from_current_line(&mut self, last: bool) {
+        loop {
+            let line = self.selected_line().to_string();
+            let sentences = tts::sentences(&line);
+            let sentence = if last {
+                sentences.last()
+            } else {
+                sentences.first()
+            };
+            if let Some(sentence) = sentence {
+                let sentence = sentence.clone();
+                self.speak(&line, sentence);
+                return;
+            }
+            let before = self.current_position();
+            if last {
+                self.move_content_up();
+            } else {
+                self.move_content_down();
+            }
+            if self.current_position() == before {
+                self.tts = None;
+                self.set_status("Reading stopped: end of book");
+                return;
+            }
+        }
+    }
+
+    fn speak(&mut self, line: &str, sentence: Range<usize>) {
+        let at = self.current_position();
+        let Some(tts) = self.tts.as_mut() else {
+            return;
+        };
+        tts.at = at;
+        tts.sentence = sentence.clone();
+        if tts.paused {
+            return;
+        }
+        if let Err(e) = tts.speak(&line[sentence]) {
+            self.tts = None;
+            self.set_status(format!("Cannot run TTS command: {}", e));
+        }
+    }
+
+    fn selected_line(&self) -> &str {
+        self.content_state
+            .selected()
+            .and_then(|i| self.view_line(i))
+            .unwrap_or("")
+    }
+
+    // line `idx` of the current view
+    fn view_line(&self, idx: usize) -> Option<&str> {
+        (idx < self.view.len())
+            .then(|| self.lines.get(self.view.start + idx))
+            .flatten()
+    }
+
+    // once a sentence is finished, go on to the next one; if the reader moved
+    // the cursor meanwhile, carry on from there instead
+    fn advance_tts(&mut self) {
+        let at = self.current_position();
+        let Some(tts) = self.tts.as_mut() else {
+            return;
+        };
+        if tts.paused || tts.is_speaking() {
+            return;
+        }
+        if tts.at != at {
+            self.read_from_current_line(false);
+        } else {
+            self.skip_sentence(true);
+        }
+    }
+
+    fn skip_sentence(&mut self, forward: bool) {
+        let Some(tts) = self.tts.as_ref() else {
+            return;
+        };
+        let current = tts.sentence.clone();
+        let line = self.selected_line().to_string();
+        let next = if forward {
+            tts::next_sentence(&line, current.end)
+        } else {
+            tts::sentences(&line)
+                .into_iter()
+                .rev()
+                .find(|s| s.end <= current.start)
+        };
+        match next {
+            Some(sentence) => self.speak(&line, sentence),
+            None => {
+                let before = self.current_position();
+                if forward {
+                    self.move_content_down();
+                } else {
+                    self.move_content_up();
+                }
+                if self.current_position() == before {
+                    self.tts = None;
+                    self.set_status("Reading stopped: end of book");
+                } else {
+                    self.read_from_current_line(!forward);
+                }
+            }
+        }
+    }
+
+    fn toggle_pause(&mut self) {
+        let line = self.selected_line().to_string();
+        if let Some(tts) = self.tts.as_mut() {
+            tts.paused = !tts.paused;
+            if tts.paused {
+                tts.stop();
+            } else {
+                // engines cannot resume mid-sentence, so start it again
+                let sentence = tts.sentence.clone();
+                self.speak(&line, sentence);
+            }
+        } else if let Some(scroll) = self.autoscroll.as_mut() {
+            scroll.toggle_pause();
+        }
+    }
+
+    fn change_speed(&mut self, faster: bool) {
+        if let Some(tts) = self.tts.as_mut() {
+            if faster {
+                tts.faster();
+            } else {
+                tts.slower();
+            }
+        } else if let Some(scroll) = self.autoscroll.as_mut() {
+            if faster {
+                scroll.faster();
+            } else {
+                scroll.slower();
+            }
+        }
+    }
+
+    fn toggle_autoscroll(&mut self) {
+        if self.autoscroll.take().is_none() {
+            self.autoscroll = Some(AutoScroll::new(self.autoscroll_config));
+            self.focus = Focus::Content;
+        }
+    }
+
+    // move down as many lines as the time since the last tick pays for,
+    // crossing chapters the same way manual scrolling does
+    fn advance_autoscroll(&mut self) {
+        let Some(scroll) = self.autoscroll.as_mut() else {
+            return;
+        };
+        scroll.accumulate(Instant::now());
+        loop {
+            let position = self.current_position();
+            // fields rather than `selected_line`, which would borrow all of self
+            let line = self
+                .content_state
+                .selected()
+                .filter(|&i| i < self.view.len())
+                .map_or("", |i| self.lines.line(self.view.start + i));
+            let Some(scroll) = self.autoscroll.as_mut() else {
+                return;
+            };
+            if !scroll.try_spend(line) {
+                return;
+            }
+            self.move_content_down();
+            if self.current_position() == position {
+                self.autoscroll = None;
+                self.set_status("Auto-scroll stopped: end of book");
+                return;
+            }
+        }
+    }
+
+    // auto-scroll speed and the time it needs for the rest of the chapter
+    fn autoscroll_info(&self) -> Option<String> {
+        let scroll = self.autoscroll.as_ref()?;
+        let from = self.content_state.selected().unwrap_or(0);
+        let left = scroll.time_for(self.lines.range(self.view.start + from..self.view.end));
+        Some(format!(
+            "{} {} left",
+            scroll.label(),
+            autoscroll::format_duration(left)
+        ))
+    }
+
+    fn current_position(&self) -> ReadingPosition {
+        ReadingPosition {
+            chapter: self.toc_state.selected().unwrap_or(0),
+            line: self.content_state.selected().unwrap_or(0),
+        }
+    }
+
+    fn save_position(&mut self) -> Result<()> {
+        if self.view.is_empty() {
+            return Ok(());
+        }
+        self.book_state.position = Some(self.current_position());
+        self.commit_session();
+        self.book_state.save(&self.file_path)
+    }
+
+    fn restore_position(&mut self) {
+        if let Some(pos) = self.book_state.position {
+            self.go_to_position(pos);
+        }
+    }
+
+    // line in the whole file of the current position
+    fn current_line(&self) -> usize {
+        self.global_line(self.content_state.selected().unwrap_or(0))
+    }
+
+    // line in the whole file of a saved position
+    fn position_line(&self, pos: ReadingPosition) -> usize {
+        self.chapters
+            .get(pos.chapter)
+            .map_or(pos.line, |c| c.start_line + pos.line)
+    }
+
+    fn go_to_position(&mut self, pos: ReadingPosition) {
+        if !self.chapters.is_empty() {
+            if pos.chapter >= self.chapters.len() {
+                return;
+            }
+            self.select_chapter(pos.chapter);
+        }
+        if !self.view.is_empty() {
+            self.content_state
+                .select(Some(pos.line.min(self.view.len() - 1)));
+        }
+        self.track_jump();
+    }
+
+    // move to the current position after `f`, remembering where we were
+    fn jump(&mut self, f: impl FnOnce(&mut Self)) {
+        let from = self.browse_start();
+        f(self);
+        self.browsing = None;
+        self.book_state.jumps.record(from, self.current_line());
+        self.track_jump();
+    }
+
+    // move the content along with a side pane without counting it as a jump;
+    // opening what was browsed to counts from where browsing started
+    fn browse(&mut self, f: impl FnOnce(&mut Self)) {
+        let from = self.browse_start();
+        f(self);
+        self.browsing = Some((from, self.current_line()));
+        self.track_jump();
+    }
+
+    // where a jump made now is from: where browsing started, unless the
+    // reader moved on since
+    fn browse_start(&self) -> usize {
+        let line = self.current_line();
+        match self.browsing {
+            Some((from, reached)) if reached == line => from,
+            _ => line,
+        }
+    }
+
+    fn jump_back(&mut self) {
+        let current = self.browse_start();
+        match self.book_state.jumps.back(current) {
+            Some(line) => self.go_to_jumped_line(line),
+            None => self.set_status("Already at the oldest jump"),
+        }
+    }
+
+    fn jump_forward(&mut self) {
+        let current = self.browse_start();
+        match self.book_state.jumps.forward(current) {
+            Some(line) => self.go_to_jumped_line(line),
+            None => self.set_status("Already at the newest jump"),
+        }
+    }
+
+    fn go_to_jumped_line(&mut self, line: usize) {
+        self.browsing = None;
+        self.go_to_global_line(line);
+        self.track_jump();
+    }
+
+    // try the likely encodings on the book, to pick one to reopen it in
+    fn open_encoding_picker(&mut self) {
+        if self.still_loading() {
+            return;
+        }
+        match loader::try_encodings(&self.file_path) {
+            Ok(trials) => {
+                let row = match self.encoding {
+                    Some(current) => trials
+                        .iter()
+                        .position(|t| t.encoding == current)
+                        .map_or(0, |i| i + 1),
+                    None => 0,
+                };
+                let mut state = ListState::default();
+                state.select(Some(row));
+                self.encoding_picker = Some(EncodingPicker { trials, state });
+            }
+            Err(e) => self.set_status(format!("Cannot try encodings: {}", e)),
+        }
+    }
+
+    fn handle_encoding_key(&mut self, key: KeyEvent) {
+        let Some(picker) = self.encoding_picker.as_mut() else {
+            return;
+        };
+        let row = picker.state.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Char('j') | KeyCode::Down if row < picker.trials.len() => {
+                picker.state.select(Some(row + 1));
+            }
+            KeyCode::Char('k') | KeyCode::Up => picker.state.select(Some(row.saturating_sub(1))),
+            KeyCode::Enter => {
+                let encoding = row
+                    .checked_sub(1)
+                    .and_then(|i| picker.trials.get(i))
+                    .map(|t| t.encoding);
+                self.encoding_picker = None;
+                self.reopen_with(encoding);
+            }
+            KeyCode::Esc | KeyCode::Char('C') | KeyCode::Char('q') => self.encoding_picker = None,
+            _ => {}
+        }
+    }
+
+    // read the book again in `encoding`, or a detected one, remembering the
+    // choice and coming back to the same place
+    fn reopen_with(&mut self, encoding: Option<Charset>) {
+        let saved = self.save_position().and_then(|()| {
+            self.book_state.encoding = encoding.map(|e| e.name().to_string());
+            self.book_state.save(&self.file_path)
+        });
+        if let Err(e) = saved {
+            self.set_status(format!("Cannot save the encoding: {}", e));
+            return;
+        }
+        self.encoding = encoding;
+        self.tracker = None;
+        self.chapters.clear();
+        self.chapter_issues.clear();
+        self.bookmarks.clear();
+        self.toc_state.select(None);
+        self.view = 0..0;
+        self.content_state.select(None);
+        self.lines = Arc::default();
+        if let Err(e) = self.load_file() {
+            self.set_status(format!("Cannot reopen the book: {}", e));
+        }
+    }
+
+    fn handle_prompt_key(&mut self, key: KeyEvent) {
+        let Some(prompt) = self.prompt.as_mut() else {
+            return;
+        };
+        let history = &self.ui_state.goto_history;
+        match key.code {
+            KeyCode::Esc => self.prompt = None,
+            KeyCode::Enter => {
+                let input = prompt.input.trim().to_string();
+                self.prompt = None;
+                self.run_goto(&input);
+            }
+            KeyCode::Tab | KeyCode::BackTab => {
+                if prompt.completion.is_none() {
+                    prompt.completions = goto::completions(&prompt.input, &self.chapters);
+                }
+                let n = prompt.completions.len();
+                if n == 0 {
+                    return;
+                }
+                let next = match (prompt.completion, key.code) {
+                    (None, KeyCode::BackTab) => n - 1,
+                    (None, _) => 0,
+                    (Some(i), KeyCode::BackTab) => (i + n - 1) % n,
+                    (Some(i), _) => (i + 1) % n,
+                };
+                prompt.completion = Some(next);
+                prompt.input = prompt.completions[next].clone();
+            }
+            KeyCode::Up if !history.is_empty() => {
+                let i = match prompt.history_index {
+                    None => {
+                        prompt.draft = prompt.input.clone();
+                        history.len() - 1
+                    }
+                    Some(i) => i.saturating_sub(1),
+                };
+                prompt.history_index = Some(i);
+                prompt.input = history[i].clone();
+            }
+            KeyCode::Down => {
+                if let Some(i) = prompt.history_index {
+                    if i + 1 < history.len() {
+                        prompt.history_index = Some(i + 1);
+                        prompt.input = history[i + 1].clone();
+                    } else {
+                        prompt.history_index = None;
+                        prompt.input = std::mem::take(&mut prompt.draft);
+                    }
+                }
+            }
+            KeyCode::Backspace => {
+                // backspace on an empty prompt closes it, as in vim
+                if prompt.input.pop().is_none() {
+                    self.prompt = None;
+                } else {
+                    prompt.completion = None;
+                }
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                prompt.input.push(c);
+                prompt.completion = None;
+            }
+            _ => {}
+        }
+    }
+
+    fn run_goto(&mut self, input: &str) {
+        let target = match goto::parse(input) {
+            Ok(Some(target)) => target,
+            Ok(None) => return,
+            Err(message) => return self.set_status(message),
+        };
+        let history = &mut self.ui_state.goto_history;
+        history.retain(|h| h != input);
+        history.push(input.to_string());
+        if history.len() > GOTO_HISTORY_LEN {
+            history.remove(0);
+        }
+        match goto::resolve(&target, &self.chapters, self.lines.len()) {
+            Ok(line) => {
+                self.jump(|app| app.go_to_global_line(line));
+                self.focus = Focus::Content;
+            }
+            Err(message) => self.set_status(message),
+        }
+    }
+
+    fn render_prompt(&self, frame: &mut Frame) {
+        let Some(prompt) = &self.prompt else {
+            return;
+        };
+        let area = frame.area();
+        let area = Rect {
+            y: area.bottom().saturating_sub(1),
+            height: 1.min(area.height),
+            ..area
+        };
+        let line = Paragraph::new(format!(":{}▏", prompt.input)).style(self.theme().footer_info);
+        frame.render_widget(Clear, area);
+        frame.render_widget(line, area);
+    }
+
+    fn save_ui_state(&self) -> Result<()> {
+        if self.ui_state == UiState::default() {
+            // nothing changed; without a saved layout the config file applies
+            return Ok(());
+        }
+        self.ui_state.save()
     }
 
     // New private method to handle initial chapter and bookmark jumps
     fn handle_initial_jumps(&mut self) -> Result<()> {
-        match (
-            self.initial_chapter_jump.take(),
-            self.initial_bookmark_jump.take(),
-        ) {
-            (Some(chapter_idx), None) => {
-                // 处理章节跳转
-                if chapter_idx < self.chapters.len() {
-                    self.select_chapter(chapter_idx.saturating_sub(1));
-                    self.focus = Focus::Content; // 跳转后聚焦内容区
-                } else {
-                    return Err(color_eyre::eyre::eyre!(
-                        "Only have {} Chapter(s). Cannot jump to chapter {}.",
+        // a jump from the command line can be undone back to the saved position
+        let from = self.book_state.position.map(|p| self.position_line(p));
+        let Some(jump) = self.initial_jump.take() else {
+            self.restore_position();
+            return Ok(());
+        };
+        match jump {
+            StartJump::Chapter(n) => {
+                // 处理章节跳转 (1-based TOC position)
+                if self.chapters.is_empty() {
+                    return Err(eyre!("The book has no chapters to jump to."));
+                }
+                if n == 0 || n > self.chapters.len() {
+                    return Err(eyre!(
+                        "Only have {} Chapter(s). Cannot jump to chapter {}; use 1 to {}, or --chapter-number for the number in the heading.",
                         self.chapters.len(),
-                        chapter_idx
+                        n,
+                        self.chapters.len()
                     ));
                 }
+                self.select_chapter(n - 1);
             }
-            (None, Some(bookmark_idx)) => {
-                // 处理书签跳转
-                if bookmark_idx < self.bookmarks.len() {
-                    self.bookmark_state
-                        .select(Some(bookmark_idx.saturating_sub(1)));
-                    self.jump_to_selected_bookmark();
-                    self.focus = Focus::Content; // 跳转后聚焦内容区
-                } else {
-                    return Err(color_eyre::eyre::eyre!(
-                        "Only have {} Bookmark(s). Cannot jump to bookmark {}.",
-                        self.bookmarks.len(),
-                        bookmark_idx
-                    ));
+            StartJump::Bookmark(n) => {
+                // 处理书签跳转 (1-based, as numbered in the bookmark menu)
+                if n == 0 || n > self.bookmarks.len() {
+                    return Err(if self.bookmarks.is_empty() {
+                        eyre!("The book has no bookmarks to jump to.")
+                    } else {
+                        eyre!(
+                            "Only have {} Bookmark(s). Cannot jump to bookmark {}; use 1 to {}.",
+                            self.bookmarks.len(),
+                            n,
+                            self.bookmarks.len()
+                        )
+                    });
                 }
+                self.bookmark_state.select(Some(n - 1));
+                self.jump_to_selected_bookmark();
             }
-            (None, None) => {}
-            (Some(_), Some(_)) => {
-                unreachable!()
+            StartJump::Target(target) => {
+                if target == goto::Target::Line(0) {
+                    return Err(eyre!("{}.", goto::NO_LINE_ZERO));
+                }
+                let line = goto::resolve(&target, &self.chapters, self.lines.len())
+                    .map_err(|message| eyre!("{}.", message))?;
+                self.go_to_global_line(line);
             }
         }
+        self.focus = Focus::Content; // 跳转后聚焦内容区
+        if let Some(from) = from {
+            self.book_state.jumps.record(from, self.current_line());
+        }
         Ok(())
     }
 
     fn render(&mut self, frame: &mut Frame) {
+        frame.render_widget(Block::default().style(self.theme().base), frame.area());
         let chunks = self.get_layout_chunks(frame.area());
         if self.show_title_footer {
             self.render_title(frame, chunks[0]);
         }
 
         let index = if self.show_title_footer { 1 } else { 0 };
-        // middle area: split into left TOC, content, and optionally bookmark
-        let middle_chunks = if self.show_bookmark_menu {
-            Layout::default()
-                .direction(Direction::Horizontal)
-                .constraints([
-                    Constraint::Length(20),
-                    Constraint::Min(1),
-                    Constraint::Length(20),
-                ])
-                .split(chunks[index])
-        } else {
-            Layout::default()
-                .direction(Direction::Horizontal)
-                .constraints([Constraint::Length(20), Constraint::Min(1)])
-                .split(chunks[index])
-        };
-
-        self.render_toc(frame, middle_chunks[0]);
-        self.render_content(frame, middle_chunks[1]);
+        // middle area: content plus whichever side panes are visible
+        let panes = self.visible_panes();
+        let constraints: Vec<Constraint> = panes
+            .iter()
+            .map(|pane| match pane {
+                Focus::Toc => Constraint::Length(self.layout.toc_width),
+                Focus::Content => Constraint::Min(1),
+                // the annotations pane sits beside the bookmarks and shares their width
+                Focus::Bookmark | Focus::Annotation => {
+                    Constraint::Length(self.layout.bookmark_width)
+                }
+            })
+            .collect();
+        let middle_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(constraints)
+            .split(chunks[index]);
 
-        if self.show_bookmark_menu {
-            self.render_bookmark_menu(frame, middle_chunks[2]);
+        self.pane_areas = panes
+            .iter()
+            .copied()
+            .zip(middle_chunks.iter().copied())
+            .collect();
+        for (pane, area) in panes.iter().zip(middle_chunks.iter()) {
+            match pane {
+                Focus::Toc => self.render_toc(frame, *area),
+                Focus::Content => self.render_content(frame, *area),
+                Focus::Bookmark => self.render_bookmark_menu(frame, *area),
+                Focus::Annotation => self.render_annotations(frame, *area),
+            }
         }
 
         if self.show_title_footer {
             self.render_footer(frame, chunks[2]);
         }
+        if self.loading.is_some() && self.chapters.is_empty() {
+            self.render_loading(frame);
+        }
+
+        if self.lookup.as_ref().is_some_and(|l| l.popup.is_some()) {
+            self.render_lookup_popup(frame);
+        }
+        if self.editing.is_some() {
+            self.render_annotation_editor(frame);
+        }
+        if self.previewing {
+            self.render_bookmark_preview(frame);
+        }
+        if self.renaming.is_some() {
+            self.render_rename_popup(frame);
+        }
+        if self.show_stats {
+            self.render_stats_popup(frame);
+        }
+        if let Some(overview) = &self.overview {
+            self.render_overview(frame, overview);
+        }
+        if self.prompt.is_some() {
+            self.render_prompt(frame);
+        }
+        if self.encoding_picker.is_some() {
+            self.render_encoding_picker(frame);
+        }
     }
 
     fn get_layout_chunks(&self, area: Rect) -> Vec<Rect> {
@@ -247,13 +1504,16 @@ impl App {
     }
 
     fn render_title(&self, frame: &mut Frame, area: Rect) {
-        let title_text = self.file_path.to_str().unwrap_or("NovelTUI");
+        let mut title_text = format!(
+            "{} · {}",
+            self.file_path.to_str().unwrap_or("NovelTUI"),
+            self.lines.encoding().name()
+        );
+        if self.lines.replaced() > 0 {
+            title_text.push_str(&format!(" · {} unreadable", self.lines.replaced()));
+        }
         let p = Paragraph::new(title_text)
-            .style(
-                Style::default()
-                    .fg(Color::Yellow)
-                    .add_modifier(Modifier::BOLD),
-            )
+            .style(self.theme().title)
             .alignment(Alignment::Center);
         frame.render_widget(p, area);
     }
@@ -263,42 +1523,39 @@ impl App {
         let block = Block::default()
             .borders(Borders::ALL)
             .border_type(BorderType::Rounded)
+            .border_style(self.theme().border)
             .title("Content");
 
         // compute available inner width for wrapping (leave 5 for borders)
         let inner_width = area.width.saturating_sub(5) as usize;
         let wrap_width = if inner_width == 0 { 1 } else { inner_width };
+        self.content_wrap_width = wrap_width;
+        if self.keep_anchor {
+            self.keep_anchor = false;
+            self.keep_selection_in_view(area.height.saturating_sub(2) as usize);
+        }
 
-        let items: Vec<ListItem> = if !self.view_lines.is_empty() {
-            self.view_lines
-                .iter()
-                .map(|line| {
+        let items: Vec<ListItem> = if !self.view.is_empty() {
+            self.lines
+                .range(self.view.clone())
+                .enumerate()
+                .map(|(idx, line)| {
                     // wrap the logical line into visual lines
-                    let wrapped = textwrap::wrap(line, wrap_width);
-                    let joined = if wrapped.is_empty() {
-                        "".to_string()
-                    } else {
-                        wrapped
-                            .iter()
-                            .map(|s| s.to_string())
-                            .collect::<Vec<_>>()
-                            .join("\n")
-                    };
-                    ListItem::new(Text::from(joined))
+                    ListItem::new(text::wrap_styled(line, wrap_width, &self.line_styles(idx)))
                 })
                 .collect()
         } else {
             vec![ListItem::new("")]
         };
 
-        // highlight style depends on focus
-        let highlight_style = if self.focus == Focus::Content {
+        // highlight style depends on focus; while reading aloud the spoken
+        // sentence is the cursor, so the line highlight would only hide it
+        let highlight_style = if self.tts.is_some() {
             Style::default()
-                .fg(Color::Black)
-                .bg(Color::White)
-                .add_modifier(Modifier::BOLD)
+        } else if self.focus == Focus::Content {
+            self.theme().content_highlight
         } else {
-            Style::default().fg(Color::Gray)
+            self.theme().inactive_highlight
         };
 
         let list = List::new(items)
@@ -310,24 +1567,112 @@ impl App {
         frame.render_stateful_widget(list, area, &mut self.content_state);
     }
 
+    // styled byte ranges within line `idx` of the current view
+    fn line_styles(&self, idx: usize) -> Vec<(Range<usize>, Style)> {
+        let mut styles = Vec::new();
+        let line = self.view_line(idx).unwrap_or("");
+        let global = self.global_line(idx);
+        for a in &self.book_state.annotations {
+            if let Some(range) = a.range_on_line(global, line) {
+                styles.push((range, a.style()));
+            }
+        }
+        if let Some((start, end)) = self.selection_bounds()
+            && (start.0..=end.0).contains(&idx)
+        {
+            let from = if idx == start.0 { start.1 } else { 0 };
+            let to = if idx == end.0 { end.1 } else { line.len() };
+            styles.push((from..to, Style::default().add_modifier(Modifier::REVERSED)));
+        }
+        if let Some(tts) = &self.tts
+            && tts.at.line == idx
+            && tts.at.chapter == self.toc_state.selected().unwrap_or(0)
+        {
+            styles.push((tts.sentence.clone(), self.theme().speech_highlight));
+        }
+        if let Some(lookup) = &self.lookup
+            && self.content_state.selected() == Some(idx)
+            && let Some(target) = lookup.targets.get(lookup.selected)
+        {
+            styles.push((target.clone(), self.theme().lookup_highlight));
+        }
+        styles
+    }
+
+    fn render_lookup_popup(&self, frame: &mut Frame) {
+        let Some((word, entries)) = self.lookup.as_ref().and_then(|l| l.popup.as_ref()) else {
+            return;
+        };
+        let scroll = self.lookup.as_ref().map_or(0, |l| l.scroll);
+        let area = centered_rect(60, 60, frame.area());
+
+        let mut lines: Vec<Line> = Vec::new();
+        if entries.is_empty() {
+            lines.push(Line::from(format!("No entry for \"{}\"", word)));
+        }
+        for (name, definition) in entries {
+            lines.push(Line::styled(
+                name.clone(),
+                Style::default().add_modifier(Modifier::BOLD),
+            ));
+            lines.extend(definition.lines().map(|l| Line::from(l.to_string())));
+            lines.push(Line::from(""));
+        }
+
+        let popup = Paragraph::new(Text::from(lines))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .border_style(self.theme().border)
+                    .title(format!(" {} ", word))
+                    .title_bottom(" [j/k]Scroll [Esc]Close "),
+            )
+            .style(self.theme().base)
+            .wrap(Wrap { trim: false })
+            .scroll((scroll, 0));
+        frame.render_widget(Clear, area);
+        frame.render_widget(popup, area);
+    }
+
     // new: render TOC (目录)
     fn render_toc(&mut self, frame: &mut Frame, area: Rect) {
+        let inner_width = area.width.saturating_sub(2) as usize;
         let items: Vec<ListItem> = if !self.chapters.is_empty() {
             self.chapters
                 .iter()
-                .map(|c| ListItem::new(c.title.clone()))
+                .zip(&self.chapter_issues)
+                .map(|(c, issues)| {
+                    // problems found by the analysis pass get a one-character marker
+                    let mut spans = Vec::new();
+                    let mut width = inner_width;
+                    if let Some(marker) = analysis::marker(issues) {
+                        spans.push(Span::styled(marker.to_string(), self.theme().footer_hint));
+                        width = width.saturating_sub(1);
+                    }
+                    if !self.layout.toc_counts {
+                        spans.push(Span::raw(truncate_to_width(&c.title, width)));
+                        return ListItem::new(Line::from(spans));
+                    }
+                    // size right-aligned after the title
+                    let count = c.counts.compact();
+                    let room = width.saturating_sub(count.width() + 1);
+                    let title = truncate_to_width(&c.title, room);
+                    let pad = width.saturating_sub(title.width() + count.width());
+                    spans.push(Span::raw(title));
+                    spans.push(Span::raw(" ".repeat(pad)));
+                    spans.push(Span::styled(count, self.theme().footer_hint));
+                    ListItem::new(Line::from(spans))
+                })
                 .collect()
         } else {
             vec![ListItem::new("NONE")]
         };
 
         let toc_highlight = if self.focus == Focus::Toc {
-            Style::default()
-                .fg(Color::Black)
-                .bg(Color::LightGreen)
-                .add_modifier(Modifier::BOLD)
+            self.theme().toc_highlight
         } else {
-            Style::default().fg(Color::Gray)
+            self.theme().inactive_highlight
         };
 
         let list = List::new(items)
@@ -335,6 +1680,7 @@ impl App {
                 Block::default()
                     .borders(Borders::ALL)
                     .border_type(BorderType::Rounded)
+                    .border_style(self.theme().border)
                     .title("TOC"),
             )
             .highlight_style(toc_highlight);
@@ -357,10 +1703,48 @@ impl App {
             height: area.height,
         };
 
-        // split into left (chapter info) and right (hints)
+        let total_lines = self.lines.len();
+        let selected_line_in_view = match self.content_state.selected() {
+            Some(idx) => idx + 1,
+            None => 0,
+        };
+        let global_line_number = self
+            .chapters
+            .get(self.toc_state.selected().unwrap_or(0))
+            .map_or(0, |chapter| chapter.start_line + selected_line_in_view);
+        let mut progress_indicator = format!(
+            "{}/{} [m]Toggle Mark [b]Bookmark",
+            global_line_number, total_lines
+        );
+        if let Some(info) = self.autoscroll_info() {
+            progress_indicator = format!("{} | {}", info, progress_indicator);
+        } else if let Some((chapter, book)) = self.time_left() {
+            progress_indicator = format!(
+                "ch {} · book {} | {}",
+                stats::format_span(chapter.as_secs()),
+                stats::format_span(book.as_secs()),
+                progress_indicator
+            );
+        }
+        if let Some(tts) = &self.tts {
+            progress_indicator = format!("{} | {}", tts.label(), progress_indicator);
+        }
+        if self.loading.is_some() {
+            progress_indicator = format!(
+                "loading: {} chapters so far | {}",
+                self.chapters.len(),
+                progress_indicator
+            );
+        }
+
+        // split into left (chapter info) and right (hints); the chapter title
+        // gets all the room the hints leave so truncated TOC titles show in full
         let cols = Layout::default()
             .direction(Direction::Horizontal)
-            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .constraints([
+                Constraint::Min(1),
+                Constraint::Length(progress_indicator.width() as u16),
+            ])
             .split(inner);
 
         let chapter_info = if !self.chapters.is_empty() {
@@ -394,42 +1778,34 @@ impl App {
             Focus::Toc => "[TOC]",
             Focus::Content => "[CONTENT]",
             Focus::Bookmark => "[BOOKMARK]",
+            Focus::Annotation => "[NOTES]",
         };
 
-        let left = Paragraph::new(format!("{} {}", focus_label, chapter_info))
+        // a pending status message replaces the chapter title until it expires
+        let left_text = match &self.status {
+            Some((message, _)) => format!("{} {}", focus_label, message),
+            None => format!("{} {}", focus_label, chapter_info),
+        };
+        let left = Paragraph::new(left_text)
             .alignment(Alignment::Left)
-            .style(Style::default().fg(Color::LightCyan));
+            .style(self.theme().footer_info);
         frame.render_widget(left, cols[0]);
 
-        let total_lines = self.lines.len();
-        let _view_lines = self.view_lines.len();
-        let selected_line_in_view = match self.content_state.selected() {
-            Some(idx) => idx + 1,
-            None => 0,
-        };
-        let global_line_number = self
-            .chapters
-            .get(self.toc_state.selected().unwrap_or(0))
-            .map_or(0, |chapter| chapter.start_line + selected_line_in_view);
-        let progress_indicator = format!(
-            "{}/{} [m]Toggle Mark [b]Bookmark",
-            global_line_number, total_lines
-        );
         //let hints = "[q]Quit [b]Bookmark [m]Toggle Mark | [h/←]Left [l/→]Right | [j/↓]Down [k/↑]Up";
         let right = Paragraph::new(progress_indicator)
             .alignment(Alignment::Right)
-            .style(Style::default().fg(Color::White));
+            .style(self.theme().footer_hint);
         frame.render_widget(right, cols[1]);
     }
 
     // helper to change selected chapter and update view
     fn select_chapter(&mut self, idx: usize) {
         if let Some(ch) = self.chapters.get(idx) {
-            self.view_lines = ch.content.clone();
+            self.view = ch.lines();
             self.view_offset = 0;
             self.toc_state.select(Some(idx));
             // reset content cursor
-            if !self.view_lines.is_empty() {
+            if !self.view.is_empty() {
                 self.content_state.select(Some(0));
             } else {
                 self.content_state.select(None);
@@ -437,75 +1813,616 @@ impl App {
         }
     }
 
-    fn handle_event(&mut self, event: Event) {
-        match event {
-            Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
-                match key_event.code {
-                    KeyCode::Char('q') => self.running = false,
+    fn handle_event(&mut self, event: Event) {
+        if matches!(event, Event::Key(_) | Event::Mouse(_) | Event::Paste(_))
+            && let Some(tracker) = self.tracker.as_mut()
+        {
+            tracker.activity();
+        }
+        let moved = match &event {
+            Event::Key(_) => true,
+            Event::Mouse(m) => m.kind != MouseEventKind::Moved,
+            _ => false,
+        };
+        if moved && let Some(loading) = self.loading.as_mut() {
+            loading.touched = true;
+        }
+        self.dispatch_event(event);
+        self.track_reading();
+    }
+
+    fn dispatch_event(&mut self, event: Event) {
+        match event {
+            Event::Key(key_event)
+                if key_event.kind == KeyEventKind::Press && self.editing.is_some() =>
+            {
+                self.handle_editor_key(key_event)
+            }
+            Event::Key(key_event)
+                if key_event.kind == KeyEventKind::Press && self.overview.is_some() =>
+            {
+                if matches!(
+                    key_event.code,
+                    KeyCode::Esc | KeyCode::Enter | KeyCode::Char('I') | KeyCode::Char('q')
+                ) {
+                    self.overview = None;
+                }
+            }
+            Event::Key(key_event) if key_event.kind == KeyEventKind::Press && self.show_stats => {
+                if matches!(
+                    key_event.code,
+                    KeyCode::Esc | KeyCode::Enter | KeyCode::Char('S') | KeyCode::Char('q')
+                ) {
+                    self.show_stats = false;
+                }
+            }
+            Event::Key(key_event)
+                if key_event.kind == KeyEventKind::Press && self.encoding_picker.is_some() =>
+            {
+                self.handle_encoding_key(key_event)
+            }
+            Event::Key(key_event)
+                if key_event.kind == KeyEventKind::Press && self.prompt.is_some() =>
+            {
+                self.handle_prompt_key(key_event)
+            }
+            Event::Paste(text) if self.prompt.is_some() => {
+                if let Some(prompt) = self.prompt.as_mut() {
+                    prompt.input.push_str(text.lines().next().unwrap_or(""));
+                }
+            }
+            Event::Key(key_event)
+                if key_event.kind == KeyEventKind::Press && self.renaming.is_some() =>
+            {
+                self.handle_rename_key(key_event)
+            }
+            Event::Paste(text) if self.renaming.is_some() => {
+                if let Some(label) = self.renaming.as_mut() {
+                    label.push_str(&text.replace(['\r', '\n'], " "));
+                }
+            }
+            Event::Key(key_event) if key_event.kind == KeyEventKind::Press && self.previewing => {
+                self.handle_preview_key(key_event)
+            }
+            Event::Paste(text) if self.editing.is_some() => {
+                if let Some(edit) = self.editing.as_mut() {
+                    edit.note.push_str(&text.replace(['\r', '\n'], " "));
+                }
+            }
+            Event::Key(key_event)
+                if key_event.kind == KeyEventKind::Press && self.selection.is_some() =>
+            {
+                self.handle_selection_key(key_event)
+            }
+            Event::Key(key_event)
+                if key_event.kind == KeyEventKind::Press && self.lookup.is_some() =>
+            {
+                self.handle_lookup_key(key_event)
+            }
+            Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
+                match key_event.code {
+                    KeyCode::Char('q') => self.running = false,
+
+                    KeyCode::Char('c') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                        self.running = false
+                    }
+
+                    KeyCode::Char('o') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                        self.jump_back()
+                    }
+                    // terminals send Ctrl-i as Tab
+                    KeyCode::Char('i') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                        self.jump_forward()
+                    }
+                    KeyCode::Tab => self.jump_forward(),
+                    KeyCode::Char(':') => self.prompt = Some(Prompt::default()),
+                    KeyCode::Char('S') => self.show_stats = !self.show_stats,
+                    KeyCode::Char('I') => self.open_overview(),
+                    KeyCode::Char('#') => self.toggle_toc_counts(),
+                    KeyCode::Char('b') => self.toggle_bookmark_menu(),
+                    KeyCode::Char('s') => self.show_title_footer = !self.show_title_footer,
+                    KeyCode::Char('t') => self.cycle_theme(),
+                    KeyCode::Char('z') => self.toggle_toc(),
+                    KeyCode::Char('B') => self.swap_bookmark_side(),
+                    KeyCode::Char('[') => self.resize_pane(Focus::Toc, -2),
+                    KeyCode::Char(']') => self.resize_pane(Focus::Toc, 2),
+                    KeyCode::Char('{') => self.resize_pane(Focus::Bookmark, -2),
+                    KeyCode::Char('}') => self.resize_pane(Focus::Bookmark, 2),
+                    KeyCode::Char('m') => self.toggle_bookmark_at_current_line(),
+                    KeyCode::Char('H') => self.toggle_heading(),
+                    KeyCode::Char('C') => self.open_encoding_picker(),
+                    KeyCode::Char('a') => self.toggle_autoscroll(),
+                    KeyCode::Char('r') => self.toggle_tts(),
+                    KeyCode::Char('d') => self.start_lookup(),
+                    KeyCode::Char('v') => self.start_selection(),
+                    KeyCode::Char('A') => self.toggle_annotations(),
+                    KeyCode::Char('E') => self.export_notes(),
+                    KeyCode::Char('e') if self.focus == Focus::Annotation => {
+                        self.edit_selected_annotation()
+                    }
+                    KeyCode::Char('x') | KeyCode::Delete if self.focus == Focus::Annotation => {
+                        self.delete_selected_annotation()
+                    }
+                    KeyCode::Char('x') | KeyCode::Delete if self.focus == Focus::Bookmark => {
+                        self.delete_selected_bookmark()
+                    }
+                    KeyCode::Char('n') if self.focus == Focus::Bookmark => {
+                        self.start_bookmark_rename()
+                    }
+                    KeyCode::Char('o') if self.focus == Focus::Bookmark => {
+                        self.cycle_bookmark_sort()
+                    }
+                    KeyCode::Char('p') if self.focus == Focus::Bookmark => {
+                        self.previewing = !self.bookmarks.is_empty()
+                    }
+                    KeyCode::Char('>') => self.skip_sentence(true),
+                    KeyCode::Char('<') => self.skip_sentence(false),
+                    KeyCode::Char(' ') => self.toggle_pause(),
+                    KeyCode::Char('+') | KeyCode::Char('=') => self.change_speed(true),
+                    KeyCode::Char('-') => self.change_speed(false),
+                    KeyCode::Char('h') | KeyCode::Left => self.switch_focus_left(),
+                    KeyCode::Char('l') | KeyCode::Right => self.switch_focus_right(),
+                    KeyCode::Char('k') | KeyCode::Up => self.handle_move_up(),
+                    KeyCode::Char('j') | KeyCode::Down => self.handle_move_down(),
+                    KeyCode::Enter => self.handle_enter(),
+                    _ => {}
+                }
+            }
+            Event::Mouse(mouse_event) => self.handle_mouse(mouse_event),
+            Event::Resize(_, _) => self.keep_anchor = true,
+            Event::FocusGained => {
+                if let Some(tracker) = self.tracker.as_mut() {
+                    tracker.set_focused(true);
+                }
+            }
+            Event::FocusLost => {
+                if let Some(tracker) = self.tracker.as_mut() {
+                    tracker.set_focused(false);
+                }
+                if let Err(e) = self.save_position() {
+                    self.set_status(format!("Cannot save position: {}", e));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        let pos = Position::new(mouse.column, mouse.row);
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                if let Some(pane) = self.border_at(pos) {
+                    self.dragging = Some(pane);
+                } else {
+                    self.click(pos);
+                }
+            }
+            MouseEventKind::Drag(MouseButton::Left) => {
+                if let Some(pane) = self.dragging {
+                    self.drag_border(pane, mouse.column);
+                }
+            }
+            MouseEventKind::Up(MouseButton::Left) => self.dragging = None,
+            MouseEventKind::ScrollUp => {
+                if let Some((pane, _)) = self.pane_at(pos) {
+                    self.scroll_pane(pane, false);
+                }
+            }
+            MouseEventKind::ScrollDown => {
+                if let Some((pane, _)) = self.pane_at(pos) {
+                    self.scroll_pane(pane, true);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn pane_at(&self, pos: Position) -> Option<(Focus, Rect)> {
+        self.pane_areas
+            .iter()
+            .find(|(_, area)| area.contains(pos))
+            .copied()
+    }
+
+    // side pane whose edge next to the content is at `pos`
+    fn border_at(&self, pos: Position) -> Option<Focus> {
+        let content = self.content_area()?;
+        self.pane_areas
+            .iter()
+            .filter(|(pane, _)| *pane != Focus::Content)
+            .find(|(_, area)| {
+                let edge = if area.x < content.x {
+                    area.right().saturating_sub(1)
+                } else {
+                    area.x
+                };
+                pos.x == edge && pos.y >= area.y && pos.y < area.bottom()
+            })
+            .map(|(pane, _)| *pane)
+    }
+
+    fn content_area(&self) -> Option<Rect> {
+        self.pane_areas
+            .iter()
+            .find(|(pane, _)| *pane == Focus::Content)
+            .map(|(_, area)| *area)
+    }
+
+    fn drag_border(&mut self, pane: Focus, column: u16) {
+        let (Some((_, area)), Some(content)) = (
+            self.pane_areas.iter().find(|(p, _)| *p == pane).copied(),
+            self.content_area(),
+        ) else {
+            return;
+        };
+        let new_width = if area.x < content.x {
+            column.saturating_sub(area.x) + 1
+        } else {
+            area.right().saturating_sub(column)
+        };
+        let current = match pane {
+            Focus::Toc => self.layout.toc_width,
+            Focus::Bookmark | Focus::Annotation => self.layout.bookmark_width,
+            Focus::Content => return,
+        };
+        self.resize_pane(pane, new_width as i16 - current as i16);
+    }
+
+    fn click(&mut self, pos: Position) {
+        let Some((pane, area)) = self.pane_at(pos) else {
+            return;
+        };
+        // rows inside the rounded border
+        if pos.y <= area.y || pos.y >= area.bottom().saturating_sub(1) {
+            self.focus = pane;
+            return;
+        }
+        let row = (pos.y - area.y - 1) as usize;
+        match pane {
+            Focus::Toc => {
+                let idx = self.toc_state.offset() + row;
+                if idx < self.chapters.len() {
+                    self.jump(|app| app.select_chapter(idx));
+                }
+            }
+            Focus::Content => {
+                if let Some(idx) = self.content_line_at_row(row) {
+                    self.content_state.select(Some(idx));
+                }
+            }
+            Focus::Bookmark => {
+                let idx = self.bookmark_state.offset() + row;
+                if idx < self.bookmarks.len() {
+                    self.bookmark_state.select(Some(idx));
+                    self.jump(Self::jump_to_selected_bookmark);
+                }
+            }
+            Focus::Annotation => {
+                let idx = self.annotation_state.offset() + row;
+                if idx < self.book_state.annotations.len() {
+                    self.annotation_state.select(Some(idx));
+                    self.jump(Self::jump_to_selected_annotation);
+                }
+            }
+        }
+        self.focus = pane;
+    }
+
+    // after a resize, move the selection instead of the view: the top line the
+    // reader was looking at stays put and the cursor lands on a visible line
+    fn keep_selection_in_view(&mut self, rows: usize) {
+        let Some(selected) = self.content_state.selected() else {
+            return;
+        };
+        let offset = self.content_state.offset();
+        if selected < offset {
+            self.content_state.select(Some(offset));
+            return;
+        }
+        let mut used = 0;
+        let mut last_visible = offset;
+        let view = self.lines.range(self.view.clone());
+        for (idx, line) in view.enumerate().skip(offset) {
+            used += textwrap::wrap(line, self.content_wrap_width).len().max(1);
+            if used > rows {
+                break;
+            }
+            last_visible = idx;
+        }
+        if selected > last_visible {
+            self.content_state.select(Some(last_visible));
+        }
+    }
+
+    // content lines wrap over several rows, so walk down from the first visible one
+    fn content_line_at_row(&self, row: usize) -> Option<usize> {
+        let mut top = 0;
+        for (idx, line) in self
+            .lines
+            .range(self.view.clone())
+            .enumerate()
+            .skip(self.content_state.offset())
+        {
+            let height = textwrap::wrap(line, self.content_wrap_width).len().max(1);
+            if row < top + height {
+                return Some(idx);
+            }
+            top += height;
+        }
+        None
+    }
+
+    fn scroll_pane(&mut self, pane: Focus, down: bool) {
+        if pane != Focus::Content {
+            // side panes move the content along, as browsing
+            return self.browse(|app| app.move_in_pane(pane, down));
+        }
+        self.move_in_pane(pane, down)
+    }
+
+    fn move_in_pane(&mut self, pane: Focus, down: bool) {
+        match (pane, down) {
+            (Focus::Toc, false) => self.move_toc_up(),
+            (Focus::Toc, true) => self.move_toc_down(),
+            (Focus::Content, false) => self.move_content_up(),
+            (Focus::Content, true) => self.move_content_down(),
+            (Focus::Bookmark, false) => self.move_bookmark_up(),
+            (Focus::Bookmark, true) => self.move_bookmark_down(),
+            (Focus::Annotation, false) => self.move_annotation(false),
+            (Focus::Annotation, true) => self.move_annotation(true),
+        }
+    }
+
+    fn start_lookup(&mut self) {
+        if self.focus != Focus::Content {
+            return;
+        }
+        if self.dictionaries.is_none() {
+            let (dicts, errors) = Dictionaries::open(&self.dictionary_paths);
+            if let Some(e) = errors.first() {
+                self.set_status(format!("Dictionary not loaded: {}", e));
+            } else if dicts.is_empty() {
+                self.set_status("No dictionaries configured");
+            }
+            self.dictionaries = Some(dicts);
+        }
+        self.lookup = Some(Lookup::default());
+        self.refresh_lookup_targets();
+    }
+
+    // recompute the pickable words after the cursor moved to another line
+    fn refresh_lookup_targets(&mut self) {
+        let targets = match &self.dictionaries {
+            Some(dicts) => dict::lookup_targets(self.selected_line(), dicts),
+            None => Vec::new(),
+        };
+        if let Some(lookup) = self.lookup.as_mut() {
+            lookup.targets = targets;
+            lookup.selected = 0;
+        }
+    }
+
+    fn handle_lookup_key(&mut self, key: KeyEvent) {
+        let Some(lookup) = self.lookup.as_mut() else {
+            return;
+        };
+        if lookup.popup.is_some() {
+            match key.code {
+                KeyCode::Char('j') | KeyCode::Down => {
+                    lookup.scroll = lookup.scroll.saturating_add(1)
+                }
+                KeyCode::Char('k') | KeyCode::Up => lookup.scroll = lookup.scroll.saturating_sub(1),
+                KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => lookup.popup = None,
+                _ => {}
+            }
+            return;
+        }
+        match key.code {
+            KeyCode::Char('h') | KeyCode::Left => {
+                lookup.selected = lookup.selected.saturating_sub(1)
+            }
+            KeyCode::Char('l') | KeyCode::Right if lookup.selected + 1 < lookup.targets.len() => {
+                lookup.selected += 1
+            }
+            KeyCode::Char('j') | KeyCode::Down => {
+                self.move_content_down();
+                self.refresh_lookup_targets();
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                self.move_content_up();
+                self.refresh_lookup_targets();
+            }
+            KeyCode::Enter | KeyCode::Char(' ') => self.look_up_selected(),
+            KeyCode::Char('E') => self.export_vocabulary(),
+            KeyCode::Esc | KeyCode::Char('d') | KeyCode::Char('q') => self.lookup = None,
+            _ => {}
+        }
+    }
+
+    fn look_up_selected(&mut self) {
+        let line = self.selected_line().to_string();
+        let Some(word) = self
+            .lookup
+            .as_ref()
+            .and_then(|l| l.targets.get(l.selected))
+            .map(|r| line[r.clone()].to_string())
+        else {
+            return;
+        };
+        let mut entries = Vec::new();
+        if let Some(dicts) = &self.dictionaries {
+            for (name, result) in dicts.lookup(&word) {
+                match result {
+                    Ok(defs) => entries.extend(defs.into_iter().map(|d| (name.clone(), d))),
+                    Err(e) => entries.push((name, format!("Error: {}", e))),
+                }
+            }
+        }
+        self.add_to_vocabulary(&word, &entries, &line);
+        if let Some(lookup) = self.lookup.as_mut() {
+            lookup.popup = Some((word, entries));
+            lookup.scroll = 0;
+        }
+    }
+
+    fn add_to_vocabulary(&mut self, word: &str, entries: &[(String, String)], context: &str) {
+        if self.book_state.vocabulary.iter().any(|v| v.word == word) {
+            return;
+        }
+        let position = self.current_position();
+        self.book_state.vocabulary.push(VocabEntry {
+            word: word.to_string(),
+            definition: entries.first().map(|(_, d)| d.clone()).unwrap_or_default(),
+            context: context.trim().to_string(),
+            chapter: position.chapter,
+            line: position.line,
+            added: state::now_secs(),
+        });
+        if let Err(e) = self.book_state.save(&self.file_path) {
+            self.set_status(format!("Cannot save vocabulary: {}", e));
+        }
+    }
+
+    // write the vocabulary next to the book as tab-separated values (Anki can import it)
+    fn export_vocabulary(&mut self) {
+        let path = self.file_path.with_extension("vocab.tsv");
+        let mut out = String::from("word\tdefinition\tcontext\n");
+        for v in &self.book_state.vocabulary {
+            let clean = |s: &str| s.split_whitespace().collect::<Vec<_>>().join(" ");
+            out.push_str(&format!(
+                "{}\t{}\t{}\n",
+                clean(&v.word),
+                clean(&v.definition),
+                clean(&v.context)
+            ));
+        }
+        match fs::write(&path, out) {
+            Ok(()) => self.set_status(format!(
+                "Exported {} words to {}",
+                self.book_state.vocabulary.len(),
+                path.display()
+            )),
+            Err(e) => self.set_status(format!("Cannot export vocabulary: {}", e)),
+        }
+    }
+
+    // write bookmarks and annotations next to the book as Markdown
+    fn export_notes(&mut self) {
+        // bookmarks are only known once the whole book is read
+        if self.still_loading() {
+            return;
+        }
+        let format = notes::Format::Md;
+        let path = self
+            .file_path
+            .with_extension(format!("notes.{}", format.extension()));
+        let notes = notes::collect(&self.chapters, &self.bookmarks, &self.book_state);
+        let result = notes::render(&notes::book_title(&self.file_path), &notes, format)
+            .and_then(|out| Ok(fs::write(&path, out)?));
+        match result {
+            Ok(()) => self.set_status(format!(
+                "Exported {} notes to {}",
+                notes.len(),
+                path.display()
+            )),
+            Err(e) => self.set_status(format!("Cannot export notes: {}", e)),
+        }
+    }
+
+    // panes currently on screen, left to right
+    fn visible_panes(&self) -> Vec<Focus> {
+        let mut panes = Vec::with_capacity(4);
+        if self.layout.bookmark_side == Side::Left {
+            if self.show_annotations {
+                panes.push(Focus::Annotation);
+            }
+            if self.show_bookmark_menu {
+                panes.push(Focus::Bookmark);
+            }
+        }
+        if self.layout.show_toc {
+            panes.push(Focus::Toc);
+        }
+        panes.push(Focus::Content);
+        if self.layout.bookmark_side == Side::Right {
+            if self.show_bookmark_menu {
+                panes.push(Focus::Bookmark);
+            }
+            if self.show_annotations {
+                panes.push(Focus::Annotation);
+            }
+        }
+        panes
+    }
+
+    fn switch_focus_left(&mut self) {
+        self.switch_focus(false);
+    }
 
-                    KeyCode::Char('c') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
-                        self.running = false
-                    }
+    fn switch_focus_right(&mut self) {
+        self.switch_focus(true);
+    }
 
-                    KeyCode::Char('b') => self.toggle_bookmark_menu(),
-                    KeyCode::Char('s') => self.show_title_footer = !self.show_title_footer,
-                    KeyCode::Char('m') => self.toggle_bookmark_at_current_line(),
-                    KeyCode::Char('h') | KeyCode::Left => self.switch_focus_left(),
-                    KeyCode::Char('l') | KeyCode::Right => self.switch_focus_right(),
-                    KeyCode::Char('k') | KeyCode::Up => self.handle_move_up(),
-                    KeyCode::Char('j') | KeyCode::Down => self.handle_move_down(),
-                    KeyCode::Enter => self.handle_enter(),
-                    _ => {}
-                }
-            }
-            _ => {}
+    // move focus to the neighbouring visible pane, wrapping around at the edges
+    fn switch_focus(&mut self, rightwards: bool) {
+        let panes = self.visible_panes();
+        let pos = panes.iter().position(|p| *p == self.focus).unwrap_or(0);
+        let next = if rightwards {
+            panes[(pos + 1) % panes.len()]
+        } else {
+            panes[(pos + panes.len() - 1) % panes.len()]
+        };
+        if self.focus == Focus::Content && next == Focus::Bookmark {
+            // shows the selected bookmark, as browsing the list does
+            self.browse(Self::jump_to_selected_bookmark);
         }
+        self.focus = next;
     }
 
-    fn switch_focus_left(&mut self) {
-        self.focus = match self.focus {
-            Focus::Bookmark => Focus::Content,
-            Focus::Content => Focus::Toc,
-            Focus::Toc => {
-                if self.show_bookmark_menu {
-                    Focus::Bookmark
-                } else {
-                    Focus::Content
-                }
-            }
+    // zen mode: hide or show the TOC
+    fn toggle_toc(&mut self) {
+        self.layout.show_toc = !self.layout.show_toc;
+        if !self.layout.show_toc && self.focus == Focus::Toc {
+            self.focus = Focus::Content;
+        }
+        self.ui_state.layout = Some(self.layout);
+    }
+
+    fn swap_bookmark_side(&mut self) {
+        self.layout.bookmark_side = match self.layout.bookmark_side {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
         };
+        self.ui_state.layout = Some(self.layout);
     }
 
-    fn switch_focus_right(&mut self) {
-        self.focus = match self.focus {
-            Focus::Toc => Focus::Content,
-            // if bookmark menu is shown, switch to it
-            // if not,go to TOC
-            Focus::Content => {
-                if self.show_bookmark_menu {
-                    self.jump_to_selected_bookmark();
-                    Focus::Bookmark
-                } else {
-                    Focus::Toc
-                }
-            }
-            Focus::Bookmark => Focus::Toc,
+    fn resize_pane(&mut self, pane: Focus, delta: i16) {
+        let width = match pane {
+            Focus::Toc => &mut self.layout.toc_width,
+            Focus::Bookmark | Focus::Annotation => &mut self.layout.bookmark_width,
+            Focus::Content => return,
         };
+        *width = width
+            .saturating_add_signed(delta)
+            .clamp(LayoutConfig::MIN_PANE_WIDTH, LayoutConfig::MAX_PANE_WIDTH);
+        self.ui_state.layout = Some(self.layout);
     }
 
     fn handle_move_up(&mut self) {
         match self.focus {
-            Focus::Toc => self.move_toc_up(),
+            Focus::Toc => self.browse(Self::move_toc_up),
             Focus::Content => self.move_content_up(),
-            Focus::Bookmark => self.move_bookmark_up(),
+            Focus::Bookmark => self.browse(Self::move_bookmark_up),
+            Focus::Annotation => self.browse(|app| app.move_annotation(false)),
         }
     }
 
     fn handle_move_down(&mut self) {
         match self.focus {
-            Focus::Toc => self.move_toc_down(),
+            Focus::Toc => self.browse(Self::move_toc_down),
             Focus::Content => self.move_content_down(),
-            Focus::Bookmark => self.move_bookmark_down(),
+            Focus::Bookmark => self.browse(Self::move_bookmark_down),
+            Focus::Annotation => self.browse(|app| app.move_annotation(true)),
         }
     }
 
@@ -537,27 +2454,28 @@ impl App {
                 // jump to previous chapter
                 if toc_sel > 0 {
                     self.select_chapter(toc_sel - 1);
-                    if !self.view_lines.is_empty() {
+                    if !self.view.is_empty() {
                         // select last line of new chapter
-                        self.content_state.select(Some(self.view_lines.len() - 1));
+                        self.content_state.select(Some(self.view.len() - 1));
                     }
                 }
             }
-        } else if !self.view_lines.is_empty() {
+        } else if !self.view.is_empty() {
             self.content_state.select(Some(0));
         }
     }
 
+    #[allow(clippy::collapsible_if)]
     fn move_content_down(&mut self) {
         if let Some(sel) = self.content_state.selected() {
-            if sel + 1 < self.view_lines.len() {
+            if sel + 1 < self.view.len() {
                 self.content_state.select(Some(sel + 1));
             } else if let Some(toc_sel) = self.toc_state.selected() {
                 if toc_sel + 1 < self.chapters.len() {
                     self.select_chapter(toc_sel + 1);
                 }
             }
-        } else if !self.view_lines.is_empty() {
+        } else if !self.view.is_empty() {
             self.content_state.select(Some(0));
         }
     }
@@ -565,10 +2483,352 @@ impl App {
     fn handle_enter(&mut self) {
         if self.focus == Focus::Toc {
             if let Some(idx) = self.toc_state.selected() {
-                self.select_chapter(idx);
+                self.jump(|app| app.select_chapter(idx));
                 self.focus = Focus::Content;
             }
+        } else if self.focus == Focus::Bookmark {
+            self.jump(Self::jump_to_selected_bookmark);
+            self.focus = Focus::Content;
+        } else if self.focus == Focus::Annotation {
+            self.jump(Self::jump_to_selected_annotation);
+            self.focus = Focus::Content;
+        }
+    }
+
+    // line number in the whole file of line `idx` of the current view
+    fn global_line(&self, idx: usize) -> usize {
+        match self.toc_state.selected().and_then(|c| self.chapters.get(c)) {
+            Some(ch) => ch.start_line + idx,
+            None => idx,
+        }
+    }
+
+    // show the chapter holding global line `line` and put the cursor on it
+    fn go_to_global_line(&mut self, line: usize) {
+        if self.chapters.is_empty() {
+            if line < self.view.len() {
+                self.content_state.select(Some(line));
+            }
+            return;
+        }
+        let chapter = self
+            .chapters
+            .iter()
+            .rposition(|c| c.start_line <= line)
+            .unwrap_or(0);
+        self.select_chapter(chapter);
+        let idx = line.saturating_sub(self.chapters[chapter].start_line);
+        if idx < self.view.len() {
+            self.content_state.select(Some(idx));
+        }
+    }
+
+    fn toggle_annotations(&mut self) {
+        self.show_annotations = !self.show_annotations;
+        if self.show_annotations {
+            self.focus = Focus::Annotation;
+            if self.annotation_state.selected().is_none() && !self.book_state.annotations.is_empty()
+            {
+                self.annotation_state.select(Some(0));
+            }
+        } else if self.focus == Focus::Annotation {
+            self.focus = Focus::Content;
+        }
+    }
+
+    fn render_annotations(&mut self, frame: &mut Frame, area: Rect) {
+        let inner_width = area.width.saturating_sub(2) as usize;
+        let items: Vec<ListItem> = self
+            .book_state
+            .annotations
+            .iter()
+            .map(|a| {
+                let text = if a.note.is_empty() {
+                    a.quote.clone()
+                } else {
+                    format!("{} — {}", a.quote, a.note)
+                };
+                ListItem::new(Line::from(vec![
+                    Span::styled(
+                        "■ ",
+                        a.style()
+                            .bg(Color::Reset)
+                            .fg(a.style().bg.unwrap_or(Color::Reset)),
+                    ),
+                    Span::raw(truncate_to_width(&text, inner_width.saturating_sub(2))),
+                ]))
+            })
+            .collect();
+        let highlight_style = if self.focus == Focus::Annotation {
+            self.theme().bookmark_highlight
+        } else {
+            self.theme().inactive_highlight
+        };
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .title("Notes")
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .border_style(self.theme().border),
+            )
+            .highlight_style(highlight_style);
+        frame.render_stateful_widget(list, area, &mut self.annotation_state);
+    }
+
+    fn move_annotation(&mut self, down: bool) {
+        let len = self.book_state.annotations.len();
+        if let Some(selected) = self.annotation_state.selected() {
+            if down && selected + 1 < len {
+                self.annotation_state.select(Some(selected + 1));
+            } else if !down && selected > 0 {
+                self.annotation_state.select(Some(selected - 1));
+            }
+            self.jump_to_selected_annotation();
+        }
+    }
+
+    fn jump_to_selected_annotation(&mut self) {
+        if let Some(a) = self
+            .annotation_state
+            .selected()
+            .and_then(|i| self.book_state.annotations.get(i))
+        {
+            let line = a.start.line;
+            self.go_to_global_line(line);
+        }
+    }
+
+    fn start_selection(&mut self) {
+        if self.focus != Focus::Content {
+            return;
+        }
+        let Some(line) = self.content_state.selected() else {
+            return;
+        };
+        let targets = self.word_targets();
+        let Some(first) = targets.first().cloned() else {
+            self.set_status("Nothing to highlight on this line");
+            return;
+        };
+        self.selection = Some(Selection {
+            anchor_line: line,
+            anchor: first,
+            targets,
+            cursor: 0,
+        });
+    }
+
+    // words on the cursor line, split the same way as for dictionary lookup
+    fn word_targets(&self) -> Vec<Range<usize>> {
+        let empty = Dictionaries::default();
+        let dicts = self.dictionaries.as_ref().unwrap_or(&empty);
+        dict::lookup_targets(self.selected_line(), dicts)
+    }
+
+    // ((line, byte), (line, byte)) spanning the anchor word and the cursor word
+    fn selection_bounds(&self) -> Option<((usize, usize), (usize, usize))> {
+        let sel = self.selection.as_ref()?;
+        let line = self.content_state.selected()?;
+        let cursor = sel.targets.get(sel.cursor)?;
+        let start = (sel.anchor_line, sel.anchor.start).min((line, cursor.start));
+        let end = (sel.anchor_line, sel.anchor.end).max((line, cursor.end));
+        Some((start, end))
+    }
+
+    fn handle_selection_key(&mut self, key: KeyEvent) {
+        let Some(sel) = self.selection.as_mut() else {
+            return;
+        };
+        match key.code {
+            KeyCode::Char('h') | KeyCode::Left => sel.cursor = sel.cursor.saturating_sub(1),
+            KeyCode::Char('l') | KeyCode::Right if sel.cursor + 1 < sel.targets.len() => {
+                sel.cursor += 1
+            }
+            // the selection stays inside the current chapter
+            KeyCode::Char('j') | KeyCode::Down => self.move_selection_line(true),
+            KeyCode::Char('k') | KeyCode::Up => self.move_selection_line(false),
+            KeyCode::Enter => self.create_annotation(),
+            KeyCode::Esc | KeyCode::Char('v') | KeyCode::Char('q') => self.selection = None,
+            _ => {}
+        }
+    }
+
+    fn move_selection_line(&mut self, down: bool) {
+        let Some(mut line) = self.content_state.selected() else {
+            return;
+        };
+        // skip lines without words, such as blank ones
+        loop {
+            let next = if down {
+                line + 1
+            } else {
+                match line.checked_sub(1) {
+                    Some(l) => l,
+                    None => return,
+                }
+            };
+            if next >= self.view.len() {
+                return;
+            }
+            line = next;
+            self.content_state.select(Some(line));
+            let targets = self.word_targets();
+            if !targets.is_empty() {
+                if let Some(sel) = self.selection.as_mut() {
+                    sel.cursor = if down { 0 } else { targets.len() - 1 };
+                    sel.targets = targets;
+                }
+                return;
+            }
+        }
+    }
+
+    fn create_annotation(&mut self) {
+        let Some(((start_line, start_byte), (end_line, end_byte))) = self.selection_bounds() else {
+            return;
+        };
+        self.selection = None;
+        let quote = (start_line..=end_line)
+            .filter_map(|i| {
+                let line = self.view_line(i)?;
+                let from = if i == start_line { start_byte } else { 0 };
+                let to = if i == end_line { end_byte } else { line.len() };
+                line.get(from..to)
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        let start = TextPos {
+            line: self.global_line(start_line),
+            char: text::byte_to_char(self.view_line(start_line).unwrap_or(""), start_byte),
+        };
+        let end = TextPos {
+            line: self.global_line(end_line),
+            char: text::byte_to_char(self.view_line(end_line).unwrap_or(""), end_byte),
+        };
+        let annotation = Annotation {
+            start,
+            end,
+            color: annotation::COLORS[0].0.to_string(),
+            note: String::new(),
+            quote,
+            created: state::now_secs(),
+        };
+        let annotations = &mut self.book_state.annotations;
+        let index = annotations.partition_point(|a| a.start <= start);
+        annotations.insert(index, annotation);
+        self.annotation_state.select(Some(index));
+        self.save_annotations();
+        self.edit_annotation(index);
+    }
+
+    fn edit_selected_annotation(&mut self) {
+        if let Some(index) = self.annotation_state.selected() {
+            self.edit_annotation(index);
+        }
+    }
+
+    fn edit_annotation(&mut self, index: usize) {
+        if let Some(a) = self.book_state.annotations.get(index) {
+            let color = annotation::COLORS
+                .iter()
+                .find(|(n, _)| *n == a.color)
+                .map_or(annotation::COLORS[0].0, |(n, _)| *n);
+            self.editing = Some(AnnotationEdit {
+                index,
+                note: a.note.clone(),
+                color,
+            });
+        }
+    }
+
+    fn handle_editor_key(&mut self, key: KeyEvent) {
+        let Some(edit) = self.editing.as_mut() else {
+            return;
+        };
+        match key.code {
+            KeyCode::Esc => self.editing = None,
+            KeyCode::Enter => {
+                if let Some(edit) = self.editing.take()
+                    && let Some(a) = self.book_state.annotations.get_mut(edit.index)
+                {
+                    a.note = edit.note.trim().to_string();
+                    a.color = edit.color.to_string();
+                    self.save_annotations();
+                }
+            }
+            KeyCode::Tab => edit.color = annotation::next_color(edit.color),
+            KeyCode::Backspace => {
+                edit.note.pop();
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => edit.note.push(c),
+            _ => {}
+        }
+    }
+
+    fn delete_selected_annotation(&mut self) {
+        let Some(index) = self.annotation_state.selected() else {
+            return;
+        };
+        if index < self.book_state.annotations.len() {
+            self.book_state.annotations.remove(index);
+            let len = self.book_state.annotations.len();
+            self.annotation_state.select(if len == 0 {
+                None
+            } else {
+                Some(index.min(len - 1))
+            });
+            self.save_annotations();
+        }
+    }
+
+    fn save_annotations(&mut self) {
+        if let Err(e) = self.book_state.save(&self.file_path) {
+            self.set_status(format!("Cannot save annotations: {}", e));
+        }
+    }
+
+    fn render_annotation_editor(&self, frame: &mut Frame) {
+        let Some(edit) = &self.editing else {
+            return;
+        };
+        let quote = self
+            .book_state
+            .annotations
+            .get(edit.index)
+            .map_or("", |a| a.quote.as_str());
+        let swatch = Annotation {
+            start: TextPos { line: 0, char: 0 },
+            end: TextPos { line: 0, char: 0 },
+            color: edit.color.to_string(),
+            note: String::new(),
+            quote: String::new(),
+            created: 0,
         }
+        .style();
+        let area = centered_rect(60, 40, frame.area());
+        let lines = vec![
+            Line::from(format!("“{}”", quote)),
+            Line::from(""),
+            Line::from(vec![
+                Span::raw("Color: "),
+                Span::styled(format!(" {} ", edit.color), swatch),
+            ]),
+            Line::from(format!("Note: {}▏", edit.note)),
+        ];
+        let popup = Paragraph::new(Text::from(lines))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .border_style(self.theme().border)
+                    .title(" Annotation ")
+                    .title_bottom(" [Tab]Color [Enter]Save [Esc]Cancel "),
+            )
+            .style(self.theme().base)
+            .wrap(Wrap { trim: false });
+        frame.render_widget(Clear, area);
+        frame.render_widget(popup, area);
     }
 
     fn toggle_bookmark_menu(&mut self) {
@@ -582,28 +2842,33 @@ impl App {
     }
 
     fn render_bookmark_menu(&mut self, frame: &mut Frame, area: Rect) {
+        let inner_width = area.width.saturating_sub(2) as usize;
         let items: Vec<ListItem> = self
             .bookmarks
             .iter()
             .enumerate() // Add enumerate to get the index
             .map(|(i, b)| {
-                ListItem::new(format!("{}. {}", i + 1, b.line_content.clone())) // Prepend with index
+                let entry = format!("{}. {}", i + 1, b.name()); // Prepend with index
+                ListItem::new(truncate_to_width(&entry, inner_width))
             })
             .collect();
         let highlight_style = if self.focus == Focus::Bookmark {
-            Style::default()
-                .add_modifier(Modifier::BOLD)
-                .bg(Color::DarkGray)
+            self.theme().bookmark_highlight
         } else {
-            Style::default().fg(Color::Gray)
+            self.theme().inactive_highlight
         };
 
+        let title = match self.ui_state.bookmark_sort {
+            BookmarkSort::Position => "Bookmarks".to_string(),
+            sort => format!("Bookmarks by {}", sort.name()),
+        };
         let list = List::new(items)
             .block(
                 Block::default()
-                    .title("Bookmarks")
+                    .title(title)
                     .borders(Borders::ALL)
-                    .border_type(BorderType::Rounded),
+                    .border_type(BorderType::Rounded)
+                    .border_style(self.theme().border),
             )
             .highlight_style(highlight_style);
 
@@ -618,65 +2883,243 @@ impl App {
         if let (Some(chapter_idx), Some(line_idx_in_view)) =
             (self.toc_state.selected(), self.content_state.selected())
         {
-            // Get immutable info before mutable borrow
-            let chapter_start_line = match self.chapters.get(chapter_idx) {
-                Some(c) => c.start_line,
-                None => return, // Chapter not found, should not happen
-            };
+            self.toggle_bookmark(chapter_idx, line_idx_in_view);
+        }
+    }
 
-            if let Some(chapter) = self.chapters.get_mut(chapter_idx) {
-                if let Some(line) = chapter.content.get_mut(line_idx_in_view) {
-                    if line.trim().is_empty() {
-                        return; // Don't bookmark empty lines
-                    }
+    fn toggle_bookmark(&mut self, chapter_idx: usize, line_idx: usize) {
+        let Some(chapter) = self.chapters.get(chapter_idx) else {
+            return; // Chapter not found, should not happen
+        };
+        if line_idx >= chapter.len() {
+            return;
+        }
+        let global_line_idx = chapter.start_line + line_idx;
+        let line = self.lines.line(global_line_idx);
+        if line.trim().is_empty() {
+            return; // Don't bookmark empty lines
+        }
 
-                    if line.trim().ends_with(BOOKMARK_SYMBOL) {
-                        // Bookmarked: remove the symbol from the end
-                        if let Some(pos) = line.rfind(BOOKMARK_SYMBOL) {
-                            let new_line = &line[..pos];
-                            *line = new_line.trim_end().to_string();
-                        }
-                    } else {
-                        // Not bookmarked: add symbol to the end
-                        *line = line.trim_end().to_string();
-                        line.push_str(&format!(" {}", BOOKMARK_SYMBOL));
-                    }
+        let old_line = line.to_string();
+        let marked = line.trim().ends_with(BOOKMARK_SYMBOL);
+        let new_line = if marked {
+            // Bookmarked: remove the symbol from the end
+            let pos = line.rfind(BOOKMARK_SYMBOL).unwrap_or(line.len());
+            line[..pos].trim_end().to_string()
+        } else {
+            // Not bookmarked: add symbol to the end
+            format!("{} {}", line.trim_end(), BOOKMARK_SYMBOL)
+        };
+        // the view reads from the book, so it shows the change too
+        let Some(book) = Arc::get_mut(&mut self.lines) else {
+            self.still_loading();
+            return;
+        };
+        book.set_line(global_line_idx, new_line);
+        if let Err(e) = self.save_file() {
+            // put the line back, so the book shows what the file holds
+            if let Some(book) = Arc::get_mut(&mut self.lines) {
+                book.set_line(global_line_idx, old_line);
+            }
+            self.set_status(format!("Error saving file after toggling bookmark: {}", e));
+            return;
+        }
+        // only once the file changed, so the saved details never disagree
+        // with it
+        let infos = &mut self.book_state.bookmarks;
+        infos.retain(|i| i.line != global_line_idx);
+        if !marked {
+            infos.push(BookmarkInfo {
+                line: global_line_idx,
+                label: String::new(),
+                created: Some(state::now_secs()),
+            });
+        }
+        self.refresh_bookmarks();
+        self.save_bookmark_info();
+    }
 
-                    if let Some(view_line) = self.view_lines.get_mut(line_idx_in_view) {
-                        *view_line = line.clone();
-                    }
+    // re-parse bookmarks, keeping the selected one selected when it still exists
+    fn refresh_bookmarks(&mut self) {
+        let marked = bookmark::marked_lines(&self.lines);
+        self.show_bookmarks(&marked);
+    }
 
-                    // Update the line in the full file content (self.lines)
-                    let global_line_idx = chapter_start_line + line_idx_in_view;
-                    if let Some(global_line) = self.lines.get_mut(global_line_idx) {
-                        *global_line = line.clone();
-                    }
+    // list the bookmarks on the `marked` lines, keeping the selection
+    #[allow(clippy::collapsible_if)]
+    fn show_bookmarks(&mut self, marked: &[usize]) {
+        let selected = self
+            .bookmark_state
+            .selected()
+            .and_then(|i| self.bookmarks.get(i))
+            .map(|b| b.line);
+        self.bookmarks = bookmark::bookmarks_on(&self.chapters, &self.lines, marked);
+        bookmark::apply_info(&mut self.bookmarks, &self.book_state.bookmarks);
+        bookmark::sort_bookmarks(&mut self.bookmarks, self.ui_state.bookmark_sort);
+        let kept = selected.and_then(|line| self.bookmarks.iter().position(|b| b.line == line));
+        if kept.is_some() {
+            self.bookmark_state.select(kept);
+        } else if self.bookmark_state.selected().is_none() && !self.bookmarks.is_empty() {
+            self.bookmark_state.select(Some(0));
+        } else if let Some(selected) = self.bookmark_state.selected() {
+            if selected >= self.bookmarks.len() {
+                self.bookmark_state.select(if self.bookmarks.is_empty() {
+                    None
+                } else {
+                    Some(self.bookmarks.len() - 1)
+                });
+            }
+        }
+    }
 
-                    // Re-parse bookmarks
-                    self.bookmarks = bookmark::parse_bookmarks(&self.chapters);
-                    if self.bookmark_state.selected().is_none() && !self.bookmarks.is_empty() {
-                        self.bookmark_state.select(Some(0));
-                    } else if let Some(selected) = self.bookmark_state.selected() {
-                        if selected >= self.bookmarks.len() {
-                            self.bookmark_state.select(if self.bookmarks.is_empty() {
-                                None
-                            } else {
-                                Some(self.bookmarks.len() - 1)
-                            });
-                        }
-                    }
+    fn save_bookmark_info(&mut self) {
+        if let Err(e) = self.book_state.save(&self.file_path) {
+            self.set_status(format!("Cannot save bookmark details: {}", e));
+        }
+    }
 
-                    // Persist changes to disk
-                    if self.save_file().is_err() {
-                        // In a real app, we'd want to handle this error, maybe show a message
-                        // pritnf error to stderr
-                        eprintln!("Error saving file after toggling bookmark.");
-                    }
-                }
+    fn delete_selected_bookmark(&mut self) {
+        if let Some(b) = self
+            .bookmark_state
+            .selected()
+            .and_then(|i| self.bookmarks.get(i))
+        {
+            let (chapter, line, name) = (b.chapter_index, b.line_in_chapter, b.name().to_string());
+            self.toggle_bookmark(chapter, line);
+            self.set_status(format!("Deleted bookmark \"{}\"", name));
+        }
+    }
+
+    fn cycle_bookmark_sort(&mut self) {
+        self.ui_state.bookmark_sort = self.ui_state.bookmark_sort.next();
+        self.refresh_bookmarks();
+        self.set_status(format!(
+            "Bookmarks sorted by {}",
+            self.ui_state.bookmark_sort.name()
+        ));
+    }
+
+    fn start_bookmark_rename(&mut self) {
+        if let Some(b) = self
+            .bookmark_state
+            .selected()
+            .and_then(|i| self.bookmarks.get(i))
+        {
+            self.renaming = Some(b.label.clone());
+        }
+    }
+
+    fn handle_rename_key(&mut self, key: KeyEvent) {
+        let Some(label) = self.renaming.as_mut() else {
+            return;
+        };
+        match key.code {
+            KeyCode::Esc => self.renaming = None,
+            KeyCode::Enter => {
+                let label = self.renaming.take().unwrap_or_default();
+                self.rename_selected_bookmark(label.trim());
+            }
+            KeyCode::Backspace => {
+                label.pop();
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => label.push(c),
+            _ => {}
+        }
+    }
+
+    fn rename_selected_bookmark(&mut self, label: &str) {
+        let Some(line) = self
+            .bookmark_state
+            .selected()
+            .and_then(|i| self.bookmarks.get(i))
+            .map(|b| b.line)
+        else {
+            return;
+        };
+        let infos = &mut self.book_state.bookmarks;
+        match infos.iter_mut().find(|i| i.line == line) {
+            Some(info) => info.label = label.to_string(),
+            None => infos.push(BookmarkInfo {
+                line,
+                label: label.to_string(),
+                created: None,
+            }),
+        }
+        self.refresh_bookmarks();
+        self.save_bookmark_info();
+    }
+
+    fn render_rename_popup(&self, frame: &mut Frame) {
+        let Some(label) = &self.renaming else {
+            return;
+        };
+        let area = centered_rect(50, 20, frame.area());
+        let popup = Paragraph::new(format!("{}▏", label))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .border_style(self.theme().border)
+                    .title(" Bookmark label ")
+                    .title_bottom(" [Enter]Save [Esc]Cancel "),
+            )
+            .style(self.theme().base)
+            .wrap(Wrap { trim: false });
+        frame.render_widget(Clear, area);
+        frame.render_widget(popup, area);
+    }
+
+    fn handle_preview_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Enter => {
+                self.previewing = false;
+                self.jump(Self::jump_to_selected_bookmark);
+                self.focus = Focus::Content;
             }
+            KeyCode::Esc | KeyCode::Char('p') | KeyCode::Char('q') => self.previewing = false,
+            _ => {}
         }
     }
 
+    // the bookmarked line with a few lines of context either side
+    fn render_bookmark_preview(&self, frame: &mut Frame) {
+        let Some(b) = self
+            .bookmark_state
+            .selected()
+            .and_then(|i| self.bookmarks.get(i))
+        else {
+            return;
+        };
+        let area = centered_rect(70, 60, frame.area());
+        let context = area.height.saturating_sub(3) as usize / 2;
+        let from = b.line.saturating_sub(context);
+        let to = (b.line + context + 1).min(self.lines.len());
+        let lines: Vec<Line> = (from..to)
+            .map(|i| {
+                let text = self.lines.line(i).to_string();
+                if i == b.line {
+                    Line::styled(text, self.theme().content_highlight)
+                } else {
+                    Line::from(text)
+                }
+            })
+            .collect();
+        let popup = Paragraph::new(Text::from(lines))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .border_style(self.theme().border)
+                    .title(format!(" {} ", b.name()))
+                    .title_bottom(" [Enter]Jump [Esc]Close "),
+            )
+            .style(self.theme().base)
+            .wrap(Wrap { trim: false });
+        frame.render_widget(Clear, area);
+        frame.render_widget(popup, area);
+    }
+
+    #[allow(clippy::collapsible_if)]
     fn move_bookmark_up(&mut self) {
         if let Some(selected) = self.bookmark_state.selected() {
             if selected > 0 {
@@ -686,6 +3129,7 @@ impl App {
         }
     }
 
+    #[allow(clippy::collapsible_if)]
     fn move_bookmark_down(&mut self) {
         if let Some(selected) = self.bookmark_state.selected() {
             if selected + 1 < self.bookmarks.len() {
@@ -695,6 +3139,7 @@ impl App {
         }
     }
 
+    #[allow(clippy::collapsible_if)]
     fn jump_to_selected_bookmark(&mut self) {
         if let Some(selected) = self.bookmark_state.selected() {
             if let Some(bookmark) = self.bookmarks.get(selected).cloned() {
@@ -704,9 +3149,60 @@ impl App {
         }
     }
 
-    fn save_file(&self) -> Result<()> {
-        let content = self.lines.join("\n");
-        fs::write(&self.file_path, content)?;
+    fn save_file(&mut self) -> Result<()> {
+        let Some(book) = Arc::get_mut(&mut self.lines) else {
+            return Err(eyre!("the book is still being loaded"));
+        };
+        book.save(&self.file_path)?;
+        // a book without a byte order mark is written as UTF-8, which the
+        // charset picked to read it no longer fits
+        if self.encoding.is_some_and(|e| e != book.encoding()) {
+            self.encoding = None;
+            self.book_state.encoding = None;
+        }
         Ok(())
     }
 }
+
+/// Cut `s` down to `width` terminal columns, ending with `…` when it does not fit.
+fn truncate_to_width(s: &str, width: usize) -> String {
+    if s.width() <= width {
+        return s.to_string();
+    }
+    if width == 0 {
+        return String::new();
+    }
+    let mut out = String::new();
+    let mut used = 0;
+    for c in s.chars() {
+        let w = c.width().unwrap_or(0);
+        // keep one column for the ellipsis
+        if used + w + 1 > width {
+            break;
+        }
+        out.push(c);
+        used += w;
+    }
+    out.push('…');
+    out
+}
+
+// a rect of the given percentage size in the middle of `area`
+fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(area);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
+}
@@ -1,18 +1,137 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
+use crate::charset::Charset;
+use crate::notes::Format;
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct Options {
     /// Path to the novel file
-    #[arg(value_name = "FILE")]
-    pub file_path: PathBuf,
+    #[arg(value_name = "FILE", required = true)]
+    pub file_path: Option<PathBuf>,
 
-    /// Jump to the bookmark number
-    #[arg(short, long, value_name = "NUM", conflicts_with = "chapter")]
+    /// Start at the NUM-th bookmark, as numbered in the bookmark menu
+    #[arg(short, long, value_name = "NUM", group = "jump")]
     pub bookmark: Option<usize>,
 
-    /// Jump to the chapter number
-    #[arg(short, long, value_name = "NUM", conflicts_with = "bookmark")]
+    /// Start at the NUM-th chapter of the TOC, counting from 1
+    #[arg(short, long, value_name = "NUM", group = "jump")]
     pub chapter: Option<usize>,
+
+    /// Start at the chapter whose heading has this number, e.g. 第300章
+    #[arg(long, value_name = "NUM", group = "jump")]
+    pub chapter_number: Option<usize>,
+
+    /// Start at this line of the file, counting from 1
+    #[arg(long, value_name = "NUM", group = "jump")]
+    pub line: Option<usize>,
+
+    /// Start this far through the file, from 0 to 100
+    #[arg(long, value_name = "PERCENT", group = "jump")]
+    pub percent: Option<f64>,
+
+    /// Leave the mouse to the terminal so text can be selected
+    #[arg(long)]
+    pub no_mouse: bool,
+
+    /// Read the book in this encoding, e.g. gbk or big5, instead of detecting it
+    #[arg(long, value_name = "NAME", value_parser = parse_encoding)]
+    pub encoding: Option<Charset>,
+
+    /// Use this config file instead of the default one
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Work with bookmarks and annotations
+    Notes {
+        #[command(subcommand)]
+        action: NotesAction,
+    },
+    /// Report missing, repeated and out-of-order chapters
+    Check {
+        /// Path to the novel file
+        #[arg(value_name = "FILE")]
+        file_path: PathBuf,
+    },
+    /// Show reading time and speed per day and week
+    Stats {
+        /// Only this book instead of every book read
+        #[arg(value_name = "FILE")]
+        file_path: Option<PathBuf>,
+    },
+    /// Rewrite books in another encoding, e.g. GBK ones as UTF-8
+    Convert {
+        /// Encoding to write, e.g. utf-8
+        #[arg(long, value_name = "NAME", value_parser = parse_encoding)]
+        to: Charset,
+
+        /// Encoding the books are in, or auto to detect it for each book
+        #[arg(long, value_name = "NAME", default_value = "auto", value_parser = parse_source)]
+        from: Source,
+
+        /// Report what would be converted without writing anything
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+
+        /// Keep each book's modification time
+        #[arg(long)]
+        keep_mtime: bool,
+
+        /// Convert books even if some characters cannot be read or written
+        #[arg(long)]
+        lossy: bool,
+
+        /// Paths to the novel files
+        #[arg(value_name = "FILE", required = true)]
+        files: Vec<PathBuf>,
+    },
+}
+
+/// The encoding to read books in for `convert`.
+#[derive(Debug, Clone, Copy)]
+pub enum Source {
+    Auto,
+    Named(Charset),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum NotesAction {
+    /// Print every bookmark and annotation of a book, grouped by chapter
+    Export {
+        /// Path to the novel file
+        #[arg(value_name = "FILE")]
+        file_path: PathBuf,
+
+        #[arg(short, long, value_enum, default_value = "md")]
+        format: Format,
+
+        /// Write to this file instead of stdout
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+}
+
+fn parse_encoding(name: &str) -> Result<Charset, String> {
+    Charset::for_label(name).ok_or_else(|| {
+        format!(
+            "unknown encoding '{}', try e.g. utf-8, gbk, big5 or shift_jis",
+            name
+        )
+    })
+}
+
+fn parse_source(name: &str) -> Result<Source, String> {
+    if name.eq_ignore_ascii_case("auto") {
+        Ok(Source::Auto)
+    } else {
+        parse_encoding(name).map(Source::Named)
+    }
 }
@@ -0,0 +1,120 @@
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+/// What the auto-scroll speed is measured in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScrollUnit {
+    Lines,
+    #[default]
+    Chars,
+}
+
+/// `[autoscroll]` section of the config.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct AutoScrollConfig {
+    pub unit: ScrollUnit,
+    /// lines or characters per minute
+    pub speed: u32,
+}
+
+impl Default for AutoScrollConfig {
+    fn default() -> Self {
+        Self {
+            unit: ScrollUnit::Chars,
+            speed: 400,
+        }
+    }
+}
+
+/// Teleprompter state: earns progress over time and spends it line by line.
+#[derive(Debug, Clone)]
+pub struct AutoScroll {
+    pub unit: ScrollUnit,
+    pub speed: u32,
+    pub paused: bool,
+    // units earned but not yet spent on moving down
+    budget: f64,
+    last: Instant,
+}
+
+impl AutoScroll {
+    pub fn new(config: AutoScrollConfig) -> Self {
+        Self {
+            unit: config.unit,
+            speed: config.speed.max(1),
+            paused: false,
+            budget: 0.0,
+            last: Instant::now(),
+        }
+    }
+
+    /// Add the progress earned since the last call.
+    pub fn accumulate(&mut self, now: Instant) {
+        if !self.paused {
+            let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+            self.budget += elapsed * self.speed as f64 / 60.0;
+        }
+        self.last = now;
+    }
+
+    /// Spend the cost of `line` if enough progress has been earned.
+    pub fn try_spend(&mut self, line: &str) -> bool {
+        let cost = self.cost_of(line);
+        if self.budget >= cost {
+            self.budget -= cost;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.last = Instant::now();
+    }
+
+    pub fn faster(&mut self) {
+        self.speed += (self.speed / 10).max(1);
+    }
+
+    pub fn slower(&mut self) {
+        self.speed = self.speed.saturating_sub((self.speed / 10).max(1)).max(1);
+    }
+
+    /// How long scrolling through `lines` takes at the current speed.
+    pub fn time_for<'a>(&self, lines: impl IntoIterator<Item = &'a str>) -> Duration {
+        let units: f64 = lines.into_iter().map(|l| self.cost_of(l)).sum();
+        Duration::from_secs_f64(units * 60.0 / self.speed as f64)
+    }
+
+    /// Short footer label, e.g. `▶ 400 cpm`.
+    pub fn label(&self) -> String {
+        let unit = match self.unit {
+            ScrollUnit::Lines => "lpm",
+            ScrollUnit::Chars => "cpm",
+        };
+        let state = if self.paused { "⏸" } else { "▶" };
+        format!("{} {} {}", state, self.speed, unit)
+    }
+
+    fn cost_of(&self, line: &str) -> f64 {
+        match self.unit {
+            ScrollUnit::Lines => 1.0,
+            // blank lines still take a moment, so never cost nothing
+            ScrollUnit::Chars => line.chars().filter(|c| !c.is_whitespace()).count().max(1) as f64,
+        }
+    }
+}
+
+/// Format a duration as `m:ss` or `h:mm:ss`.
+pub fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
@@ -1,4 +1,8 @@
+use serde::{Deserialize, Serialize};
+
 use crate::chapter::Chapter;
+use crate::loader::Book;
+use crate::state::BookmarkInfo;
 
 pub const BOOKMARK_SYMBOL: &str = "🔖";
 
@@ -7,28 +11,110 @@ pub struct Bookmark {
     pub chapter_index: usize,
     pub line_in_chapter: usize,
     pub line_content: String,
+    /// line in the whole file
+    pub line: usize,
+    /// user-given name, empty when unnamed
+    pub label: String,
+    /// seconds since the Unix epoch, unknown for bookmarks made outside noveltui
+    pub created: Option<u64>,
+}
+
+impl Bookmark {
+    /// The label, or the bookmarked text when there is none.
+    pub fn name(&self) -> &str {
+        if self.label.is_empty() {
+            &self.line_content
+        } else {
+            &self.label
+        }
+    }
+}
+
+/// Order of the bookmark menu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BookmarkSort {
+    #[default]
+    Position,
+    Created,
+    Label,
 }
 
-pub fn parse_bookmarks(chapters: &Vec<Chapter>) -> Vec<Bookmark> {
-    let mut bookmarks = Vec::new();
-    for (i, chapter) in chapters.iter().enumerate() {
-        for (j, line) in chapter.content.iter().enumerate() {
-            if line.trim().ends_with(BOOKMARK_SYMBOL) {
-                let content = line
-                    .trim()
-                    .strip_suffix(BOOKMARK_SYMBOL)
-                    .unwrap_or("")
-                    .trim()
-                    .to_string();
-                if !content.is_empty() {
-                    bookmarks.push(Bookmark {
-                        chapter_index: i,
-                        line_in_chapter: j,
-                        line_content: content,
-                    });
-                }
-            }
+impl BookmarkSort {
+    pub fn next(self) -> Self {
+        match self {
+            BookmarkSort::Position => BookmarkSort::Created,
+            BookmarkSort::Created => BookmarkSort::Label,
+            BookmarkSort::Label => BookmarkSort::Position,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BookmarkSort::Position => "position",
+            BookmarkSort::Created => "time",
+            BookmarkSort::Label => "label",
         }
     }
-    bookmarks
+}
+
+pub fn parse_bookmarks(chapters: &[Chapter], lines: &Book) -> Vec<Bookmark> {
+    bookmarks_on(chapters, lines, &marked_lines(lines))
+}
+
+/// Lines ending in the bookmark symbol, in or out of chapters.
+pub fn marked_lines(lines: &Book) -> Vec<usize> {
+    lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.trim_end().ends_with(BOOKMARK_SYMBOL))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Bookmarks on those of the `marked` lines, in order, that are in a chapter.
+pub fn bookmarks_on(chapters: &[Chapter], lines: &Book, marked: &[usize]) -> Vec<Bookmark> {
+    marked
+        .iter()
+        .filter_map(|&line| {
+            let i = chapters
+                .partition_point(|c| c.start_line <= line)
+                .checked_sub(1)?;
+            let chapter = chapters.get(i).filter(|c| line < c.end_line)?;
+            let content = lines
+                .line(line)
+                .trim()
+                .strip_suffix(BOOKMARK_SYMBOL)?
+                .trim()
+                .to_string();
+            (!content.is_empty()).then(|| Bookmark {
+                chapter_index: i,
+                line_in_chapter: line - chapter.start_line,
+                line_content: content,
+                line,
+                label: String::new(),
+                created: None,
+            })
+        })
+        .collect()
+}
+
+/// Fill in labels and creation times kept outside the book file.
+pub fn apply_info(bookmarks: &mut [Bookmark], infos: &[BookmarkInfo]) {
+    for b in bookmarks {
+        if let Some(info) = infos.iter().find(|i| i.line == b.line) {
+            b.label = info.label.clone();
+            b.created = info.created;
+        }
+    }
+}
+
+/// Sort bookmarks, which come from `parse_bookmarks` in position order.
+pub fn sort_bookmarks(bookmarks: &mut [Bookmark], sort: BookmarkSort) {
+    match sort {
+        BookmarkSort::Position => bookmarks.sort_by_key(|b| b.line),
+        // bookmarks of unknown age come first, as they predate the others
+        BookmarkSort::Created => bookmarks.sort_by_key(|b| (b.created, b.line)),
+        BookmarkSort::Label => bookmarks.sort_by_key(|b| (b.name().to_lowercase(), b.line)),
+    }
 }
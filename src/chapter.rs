@@ -1,64 +1,580 @@
-use regex::Regex;
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Range;
 
-#[derive(Debug, Clone)]
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+
+use crate::loader::Book;
+use crate::number;
+use crate::text::{self, Counts};
+
+/// Headings longer than this (in characters) are taken for body text.
+const MAX_HEADING_CHARS: usize = 50;
+/// Headings up to this long look like headings; longer ones lose a point.
+const SHORT_HEADING_CHARS: usize = 30;
+/// Text before `第N章` that still counts as a volume or section prefix.
+const MAX_PREFIX_CHARS: usize = 12;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Chapter {
     pub number: usize,
     pub title: String,
+    /// a `Book II` or `Part One` heading, numbered apart from the chapters
+    pub part: bool,
     pub start_line: usize,
-    pub content: Vec<String>,
+    /// first line after the chapter
+    pub end_line: usize,
+    /// size of the chapter text, heading included
+    pub counts: Counts,
+}
+
+impl Chapter {
+    /// Global lines of the chapter, heading included.
+    pub fn lines(&self) -> Range<usize> {
+        self.start_line..self.end_line
+    }
+
+    pub fn len(&self) -> usize {
+        self.end_line - self.start_line
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Headings the reader corrected by hand, by global line.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeadingOverrides {
+    /// detected headings that are really body text
+    pub demoted: BTreeSet<usize>,
+    /// lines that start a chapter though nothing detected them
+    pub promoted: BTreeSet<usize>,
+}
+
+impl HeadingOverrides {
+    /// Turn the heading at `line` into body text, or the other way round.
+    pub fn toggle(&mut self, line: usize, is_heading: bool) {
+        if is_heading {
+            self.promoted.remove(&line);
+            self.demoted.insert(line);
+        } else {
+            self.demoted.remove(&line);
+            self.promoted.insert(line);
+        }
+    }
+}
+
+// a line the heading patterns matched, before deciding if it is one
+struct Candidate<'a> {
+    number: usize,
+    rest: &'a str,
+    // `第N章`, or the English word it starts with: `Chapter`, `Book` or `Part`
+    label: &'static str,
+    // text before `第`; always empty for English headings
+    prefix: &'a str,
+    // whether whitespace or punctuation separates the heading from the rest
+    spaced: bool,
+}
+
+struct Patterns {
+    cn: Regex,
+    en: Regex,
+}
+
+impl Patterns {
+    fn new() -> Self {
+        Self {
+            cn: Regex::new(r"^\s*(.*?)第\s*(\d+)\s*章(\s*)(.*)$").unwrap(),
+            en: Regex::new(r"(?i)^\s*(chapter|book|part)\s*(.*)$").unwrap(),
+        }
+    }
+
+    fn candidate<'a>(&self, line: &'a str) -> Option<Candidate<'a>> {
+        let get = |caps: &Captures<'a>, i: usize| caps.get(i).map_or("", |m| m.as_str());
+        // most lines are neither, and these checks are much cheaper than the regexes
+        let cn = line.contains('章');
+        let en = line
+            .trim_start()
+            .starts_with(|c: char| matches!(c.to_ascii_lowercase(), 'c' | 'b' | 'p'));
+        if !cn && !en {
+            return None;
+        }
+        if let Some(caps) = cn.then(|| self.cn.captures(line)).flatten() {
+            return Some(Candidate {
+                number: get(&caps, 2).parse().unwrap_or(0),
+                rest: get(&caps, 4).trim(),
+                label: CN,
+                prefix: get(&caps, 1).trim(),
+                spaced: !get(&caps, 3).is_empty(),
+            });
+        }
+        let caps = self.en.captures(line)?;
+        let (number, after) = number::parse(get(&caps, 2))?;
+        let rest = after.trim_start_matches(|c: char| c.is_whitespace() || ":.-–—".contains(c));
+        Some(Candidate {
+            number,
+            rest: rest.trim(),
+            label: match get(&caps, 1).to_ascii_lowercase().as_str() {
+                "book" => "Book",
+                "part" => "Part",
+                _ => "Chapter",
+            },
+            prefix: "",
+            spaced: rest.len() < after.len(),
+        })
+    }
 }
 
-pub fn parse_lines(lines: &[String]) -> Vec<Chapter> {
-    let re_cn = Regex::new(r".*第\s*(\d+)\s*章(?:\s*(.*))?$").unwrap();
-    let re_en = Regex::new(r"(?i)^\s*chapter\s*(\d+)\s*[:\.\s-]*\s*(.*)?$").unwrap();
+const CN: &str = "第章";
+
+impl Candidate<'_> {
+    fn title(&self) -> String {
+        let head = if self.label == CN {
+            format!("第{}章", self.number)
+        } else {
+            format!("{} {}", self.label, self.number)
+        };
+        if self.rest.is_empty() {
+            head
+        } else {
+            format!("{} {}", head, self.rest)
+        }
+    }
+
+    // Whether the match at `line` is a heading rather than a mention in the
+    // text, e.g. `他翻到第3章的时候`. Each sign for or against adds or takes a
+    // point; `blank_around` is whether an empty line (or the file edge) is
+    // next to it, `last` the number of the heading before.
+    fn is_heading(&self, line: &str, blank_around: bool, last: Option<usize>) -> bool {
+        let len = line.trim().chars().count();
+        if len > MAX_HEADING_CHARS {
+            return false;
+        }
+        // `第3章的时候`, `第3章，他…`: the sentence goes on
+        if !self.spaced && self.rest.starts_with(is_continuation) {
+            return false;
+        }
+        let mut score: i32 = 0;
+        if self.prefix.is_empty() {
+            score += 2;
+        } else if self.prefix.chars().count() > MAX_PREFIX_CHARS
+            || self.prefix.chars().any(is_sentence_punctuation)
+        {
+            return false;
+        } else if !is_volume_prefix(self.prefix) {
+            // `他翻到第121章` rather than `第一卷 第1章`
+            score -= 1;
+        }
+        if blank_around {
+            score += 1;
+        }
+        if len > SHORT_HEADING_CHARS {
+            score -= 1;
+        }
+        if self.rest.ends_with(['。', '，', '.', ',']) {
+            score -= 1;
+        }
+        // `Part one of the plan was…`
+        if self.label != CN && self.rest.starts_with(char::is_lowercase) {
+            score -= 2;
+        }
+        score += match last {
+            None => 1,
+            Some(n) if self.number == n + 1 => 1,
+            Some(n) if self.number > n => 0,
+            Some(_) => -1,
+        };
+        score >= 1
+    }
+}
+
+fn is_sentence_punctuation(c: char) -> bool {
+    matches!(
+        c,
+        '，' | '。' | '！' | '？' | '；' | '“' | '”' | '…' | ',' | '.' | '!' | '?' | ';' | '"'
+    )
+}
+
+// characters that carry a sentence on straight after `第N章`
+fn is_continuation(c: char) -> bool {
+    matches!(c, '的' | '、') || is_sentence_punctuation(c)
+}
+
+fn is_volume_prefix(prefix: &str) -> bool {
+    ["卷", "部", "篇", "集", "正文", "VIP"]
+        .iter()
+        .any(|p| prefix.contains(p))
+}
+
+/// Split `lines` into chapters, applying the reader's `overrides`. Books
+/// without numbered headings are split by layout instead, see [`fallback`].
+pub fn parse_lines(lines: &Book, overrides: &HeadingOverrides) -> Vec<Chapter> {
+    parse_lines_streaming(lines, overrides, |_| {})
+}
+
+/// Like [`parse_lines`], handing each chapter to `found` as soon as the next
+/// heading ends it. Chapters found by layout only come in the returned list.
+pub fn parse_lines_streaming(
+    lines: &Book,
+    overrides: &HeadingOverrides,
+    mut found: impl FnMut(Chapter),
+) -> Vec<Chapter> {
+    let patterns = Patterns::new();
+    let blank = |i: usize| lines.get(i).is_none_or(|l| l.trim().is_empty());
+    let mut headings: Vec<Heading> = Vec::new();
+    // chapters ended by the headings after them
     let mut chapters: Vec<Chapter> = Vec::new();
-    let mut current: Option<Chapter> = None;
+    // number of the last heading of each kind, so parts and chapters are
+    // numbered apart
+    let mut last_numbers: HashMap<&str, usize> = HashMap::new();
+    let mut detected = false;
 
     for (i, line) in lines.iter().enumerate() {
-        let caps = re_cn.captures(line).or_else(|| re_en.captures(line));
-        if let Some(caps) = caps {
-            // push previous
-            if let Some(prev) = current.take() {
-                chapters.push(prev);
+        let heading = if overrides.demoted.contains(&i) {
+            None
+        } else if overrides.promoted.contains(&i) {
+            Some(match patterns.candidate(line) {
+                Some(c) => (c.label, c.number, c.title()),
+                None => ("", 0, line.trim().to_string()),
+            })
+        } else {
+            let blank_around = i == 0 || blank(i - 1) || blank(i + 1);
+            patterns
+                .candidate(line)
+                .filter(|c| c.is_heading(line, blank_around, last_numbers.get(c.label).copied()))
+                .map(|c| (c.label, c.number, c.title()))
+                .inspect(|_| detected = true)
+        };
+        if let Some((label, number, title)) = heading {
+            if number > 0 {
+                last_numbers.insert(label, number);
+            }
+            if let Some(prev) = headings.last() {
+                let chapter = chapter_of(lines, prev, i);
+                found(chapter.clone());
+                chapters.push(chapter);
             }
-            let num = caps
-                .get(1)
-                .and_then(|m| m.as_str().parse::<usize>().ok())
-                .unwrap_or(0);
-            let rest = caps
-                .get(2)
-                .map(|m| m.as_str().trim().to_string())
-                .unwrap_or_default();
-            let is_cn = re_cn.is_match(line);
-            let title = if rest.is_empty() {
-                if is_cn {
-                    format!("第{}章", num)
-                } else {
-                    format!("Chapter {}", num)
-                }
-            } else {
-                if is_cn {
-                    format!("第{}章 {}", num, rest)
-                } else {
-                    format!("Chapter {} {}", num, rest)
-                }
-            };
-            current = Some(Chapter {
-                number: num,
+            headings.push(Heading {
+                line: i,
+                number,
                 title,
-                start_line: i,
-                content: vec![line.clone()],
+                part: matches!(label, "Book" | "Part"),
             });
-        } else {
-            if let Some(ref mut ch) = current {
-                ch.content.push(line.clone());
+        }
+    }
+
+    if detected {
+        if let Some(last) = headings.last() {
+            chapters.push(chapter_of(lines, last, lines.len()));
+        }
+        return chapters;
+    }
+    // lines promoted by hand join the layout headings
+    let promoted: BTreeSet<usize> = headings.iter().map(|h| h.line).collect();
+    headings.extend(
+        fallback(lines, &overrides.demoted)
+            .into_iter()
+            .filter(|h| !promoted.contains(&h.line)),
+    );
+    headings.sort_by_key(|h| h.line);
+    split(lines, &headings)
+}
+
+// where a chapter starts and what the TOC calls it
+struct Heading {
+    line: usize,
+    number: usize,
+    title: String,
+    part: bool,
+}
+
+// chapters from their headings; lines before the first heading are left out
+fn split(lines: &Book, headings: &[Heading]) -> Vec<Chapter> {
+    headings
+        .iter()
+        .enumerate()
+        .map(|(i, h)| {
+            let end = headings.get(i + 1).map_or(lines.len(), |next| next.line);
+            chapter_of(lines, h, end)
+        })
+        .collect()
+}
+
+fn chapter_of(lines: &Book, h: &Heading, end: usize) -> Chapter {
+    Chapter {
+        number: h.number,
+        title: h.title.clone(),
+        part: h.part,
+        start_line: h.line,
+        end_line: end,
+        counts: lines
+            .range(h.line..end)
+            .map(text::count)
+            .fold(Counts::default(), |a, b| a + b),
+    }
+}
+
+/// Lines per section when a book has nothing that looks like a heading.
+const SECTION_LINES: usize = 200;
+/// How far past `SECTION_LINES` a section may run to end at a blank line.
+const SECTION_SLACK: usize = 20;
+/// Short lines standing alone only count as headings this far apart.
+const MIN_SECTION_LINES: usize = 20;
+/// Standalone lines up to this many characters may be headings.
+const MAX_LAYOUT_HEADING_CHARS: usize = 40;
+const MAX_SHORT_HEADING_CHARS: usize = 20;
+
+/// Headings for a book the numbered patterns found nothing in, from layout:
+/// Roman numerals or all-caps lines on their own, separators such as `***`,
+/// or short lines set apart by blank lines, taking the first kind that splits
+/// the book. Failing those, the book is cut into sections of about
+/// `SECTION_LINES` lines so the TOC and progress still work.
+fn fallback(lines: &Book, demoted: &BTreeSet<usize>) -> Vec<Heading> {
+    let blank = |i: usize| lines.get(i).is_none_or(|l| l.trim().is_empty());
+    // a line alone between blank lines (or the file edges)
+    let standalone = |i: usize| {
+        !demoted.contains(&i)
+            && !blank(i)
+            && (i == 0 || blank(i - 1))
+            && blank(i + 1)
+            && lines.line(i).trim().chars().count() <= MAX_LAYOUT_HEADING_CHARS
+    };
+    let titled = |i: usize, number: usize| Heading {
+        line: i,
+        number,
+        title: lines.line(i).trim().to_string(),
+        part: false,
+    };
+
+    let roman: Vec<Heading> = (0..lines.len())
+        .filter(|&i| standalone(i))
+        .filter_map(|i| {
+            let word = lines.line(i).trim().trim_end_matches('.');
+            let upper = word.chars().all(|c| c.is_ascii_uppercase());
+            number::roman(word).filter(|_| upper).map(|n| titled(i, n))
+        })
+        .collect();
+    if roman.len() >= 2 {
+        return roman;
+    }
+
+    let caps: Vec<Heading> = (0..lines.len())
+        .filter(|&i| standalone(i) && is_all_caps(lines.line(i)))
+        .map(|i| titled(i, 0))
+        .collect();
+    if caps.len() >= 3 {
+        return caps;
+    }
+
+    let separators: Vec<usize> = (0..lines.len())
+        .filter(|&i| !demoted.contains(&i) && is_separator(lines.line(i)))
+        .collect();
+    if !separators.is_empty() {
+        let text_before = (0..separators[0]).any(|i| !blank(i));
+        let starts = text_before.then_some(0).into_iter().chain(separators);
+        return numbered_sections(starts);
+    }
+
+    let mut short: Vec<Heading> = Vec::new();
+    for i in (0..lines.len()).filter(|&i| standalone(i)) {
+        let line = lines.line(i).trim();
+        if line.chars().count() > MAX_SHORT_HEADING_CHARS
+            || line.ends_with(|c: char| is_sentence_punctuation(c) || c == '\'' || c == '’')
+            || !line.chars().any(char::is_alphanumeric)
+        {
+            continue;
+        }
+        match short.last().map(|h| h.line) {
+            // a book title right before the first heading, say
+            Some(prev) if (prev + 1..i).all(blank) => {
+                short.pop();
+                short.push(titled(i, 0));
             }
+            Some(prev) if i - prev < MIN_SECTION_LINES => {}
+            _ => short.push(titled(i, 0)),
         }
     }
+    if short.len() >= 3 {
+        return short;
+    }
 
-    if let Some(last) = current {
-        chapters.push(last);
+    let mut starts = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        starts.push(i);
+        let target = i + SECTION_LINES;
+        // end the section at a paragraph break when there is one close by
+        i = (target..(target + SECTION_SLACK).min(lines.len()))
+            .find(|&j| blank(j))
+            .map_or(target, |j| j + 1);
     }
+    numbered_sections(starts)
+}
+
+fn numbered_sections(starts: impl IntoIterator<Item = usize>) -> Vec<Heading> {
+    starts
+        .into_iter()
+        .enumerate()
+        .map(|(n, line)| Heading {
+            line,
+            number: n + 1,
+            title: format!("Section {}", n + 1),
+            part: false,
+        })
+        .collect()
+}
+
+// `***`, `* * *`, `-----`, `~~~` and the like
+fn is_separator(line: &str) -> bool {
+    let mut marks = line.chars().filter(|c| !c.is_whitespace());
+    let Some(first) = marks.next() else {
+        return false;
+    };
+    let rest: Vec<char> = marks.collect();
+    "*-=_~#·•".contains(first) && rest.len() >= 2 && rest.iter().all(|&c| c == first)
+}
+
+fn is_all_caps(line: &str) -> bool {
+    let letters = line.chars().filter(|c| c.is_alphabetic()).count();
+    letters >= 3 && !line.chars().any(char::is_lowercase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    chapters
+    fn is_heading(line: &str, blank_around: bool, last: Option<usize>) -> bool {
+        Patterns::new()
+            .candidate(line)
+            .is_some_and(|c| c.is_heading(line, blank_around, last))
+    }
+
+    #[test]
+    fn heading_on_its_own_line_is_a_heading() {
+        assert!(is_heading("第3章 出发", true, Some(2)));
+        assert!(is_heading("第一卷 第3章 出发", false, Some(2)));
+        assert!(is_heading("Chapter 3: The Road", true, None));
+    }
+
+    #[test]
+    fn mention_in_the_text_is_not_a_heading() {
+        assert!(!is_heading("他翻到第3章的时候", true, Some(2)));
+        assert!(!is_heading("他说：第3章，我看完了", true, Some(2)));
+        assert!(!is_heading("Part of the plan was to wait.", true, None));
+    }
+
+    #[test]
+    fn overlong_line_is_not_a_heading() {
+        let line = format!("第3章 {}", "很".repeat(MAX_HEADING_CHARS));
+        assert!(!is_heading(&line, true, Some(2)));
+    }
+
+    #[test]
+    fn out_of_order_number_counts_against_a_heading() {
+        // a prefix that is not a volume leaves the order to decide
+        assert!(is_heading("外传 第3章 出发", true, Some(2)));
+        assert!(!is_heading("外传 第3章 出发", true, Some(7)));
+    }
+
+    fn book(lines: &[&str]) -> Book {
+        Book::from_string(lines.join("\n"))
+    }
+
+    // `n` lines of one paragraph, so none of them stands alone
+    fn paragraph(n: usize) -> Vec<&'static str> {
+        vec!["The rain kept on all night."; n]
+    }
+
+    fn fallback_lines(book: &Book) -> Vec<(usize, String)> {
+        fallback(book, &BTreeSet::new())
+            .into_iter()
+            .map(|h| (h.line, h.title))
+            .collect()
+    }
+
+    #[test]
+    fn fallback_takes_roman_numerals() {
+        let book = book(&["I", "", "It began.", "", "II.", "", "It went on."]);
+        assert_eq!(
+            fallback_lines(&book),
+            [(0, "I".to_string()), (4, "II.".to_string())]
+        );
+    }
+
+    #[test]
+    fn fallback_takes_all_caps_lines() {
+        let mut lines = Vec::new();
+        for title in ["THE STORM", "AFTER", "HOME AGAIN"] {
+            lines.extend([title, ""]);
+            lines.extend(paragraph(3));
+            lines.push("");
+        }
+        let headings: Vec<usize> = fallback_lines(&book(&lines))
+            .into_iter()
+            .map(|(line, _)| line)
+            .collect();
+        assert_eq!(headings, [0, 6, 12]);
+    }
+
+    #[test]
+    fn fallback_splits_at_separators() {
+        let book = book(&["It began.", "***", "It went on.", "* * *", "It ended."]);
+        assert_eq!(
+            fallback_lines(&book),
+            [
+                (0, "Section 1".to_string()),
+                (1, "Section 2".to_string()),
+                (3, "Section 3".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn fallback_takes_short_standalone_lines() {
+        let mut lines = vec!["The Book of Rain", ""];
+        for title in ["Morning", "Noon", "Night"] {
+            lines.extend([title, ""]);
+            lines.extend(paragraph(MIN_SECTION_LINES));
+            lines.push("");
+        }
+        // the book title right before the first heading is left out
+        let headings: Vec<String> = fallback_lines(&book(&lines))
+            .into_iter()
+            .map(|(_, title)| title)
+            .collect();
+        assert_eq!(headings, ["Morning", "Noon", "Night"]);
+    }
+
+    #[test]
+    fn fallback_cuts_sections_of_fixed_length() {
+        let book = book(&paragraph(2 * SECTION_LINES + 50));
+        let starts: Vec<usize> = fallback_lines(&book)
+            .into_iter()
+            .map(|(line, _)| line)
+            .collect();
+        assert_eq!(starts, [0, SECTION_LINES, 2 * SECTION_LINES]);
+    }
+
+    #[test]
+    fn fallback_is_not_used_with_numbered_headings() {
+        let book = book(&[
+            "Chapter 1",
+            "",
+            "It began.",
+            "***",
+            "It went on.",
+            "",
+            "Chapter 2",
+            "",
+            "It ended.",
+        ]);
+        let chapters = parse_lines(&book, &HeadingOverrides::default());
+        let found: Vec<(usize, &str)> = chapters
+            .iter()
+            .map(|c| (c.start_line, c.title.as_str()))
+            .collect();
+        assert_eq!(found, [(0, "Chapter 1"), (6, "Chapter 2")]);
+    }
 }
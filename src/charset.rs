@@ -0,0 +1,189 @@
+use std::io::{self, Write};
+
+use encoding_rs::{EncoderResult, Encoding, UTF_8, UTF_16BE, UTF_16LE};
+
+/// What a book's bytes are read as: an encoding `encoding_rs` knows, or
+/// UTF-32, which it leaves out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    Encoding(&'static Encoding),
+    Utf32Le,
+    Utf32Be,
+}
+
+impl Default for Charset {
+    fn default() -> Self {
+        Charset::Encoding(UTF_8)
+    }
+}
+
+impl Charset {
+    /// The charset called `label`, e.g. `gbk`, `big5`, `utf-16le` or `utf-32`.
+    pub fn for_label(label: &str) -> Option<Self> {
+        let label = label.trim();
+        match label.to_ascii_lowercase().as_str() {
+            "utf-32" | "utf-32le" | "utf32" => Some(Charset::Utf32Le),
+            "utf-32be" => Some(Charset::Utf32Be),
+            _ => Encoding::for_label(label.as_bytes()).map(Charset::Encoding),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Charset::Encoding(e) => e.name(),
+            Charset::Utf32Le => "UTF-32LE",
+            Charset::Utf32Be => "UTF-32BE",
+        }
+    }
+
+    /// The charset named by a byte order mark at the start of `bytes`, and
+    /// the mark's length.
+    pub fn from_bom(bytes: &[u8]) -> Option<(Self, usize)> {
+        // UTF-32LE's mark starts with UTF-16LE's, so it goes first
+        match bytes {
+            [0xFF, 0xFE, 0, 0, ..] => Some((Charset::Utf32Le, 4)),
+            [0, 0, 0xFE, 0xFF, ..] => Some((Charset::Utf32Be, 4)),
+            _ => Encoding::for_bom(bytes).map(|(e, len)| (Charset::Encoding(e), len)),
+        }
+    }
+
+    /// The byte order mark of a Unicode charset; empty for the others.
+    pub fn bom(self) -> &'static [u8] {
+        match self {
+            Charset::Encoding(e) if e == UTF_8 => b"\xEF\xBB\xBF",
+            Charset::Encoding(e) if e == UTF_16LE => b"\xFF\xFE",
+            Charset::Encoding(e) if e == UTF_16BE => b"\xFE\xFF",
+            Charset::Encoding(_) => b"",
+            Charset::Utf32Le => b"\xFF\xFE\x00\x00",
+            Charset::Utf32Be => b"\x00\x00\xFE\xFF",
+        }
+    }
+
+    /// A decoder that takes the text as it comes; byte order marks are left
+    /// to the caller.
+    pub fn decoder(self) -> Decoder {
+        match self {
+            Charset::Encoding(e) => Decoder::Encoding(e.new_decoder_without_bom_handling()),
+            Charset::Utf32Le => Decoder::Utf32 { big_endian: false },
+            Charset::Utf32Be => Decoder::Utf32 { big_endian: true },
+        }
+    }
+
+    /// Write `text` to `out` in a Unicode charset. Other encodings get UTF-8,
+    /// as noveltui writes books.
+    pub fn write(self, out: &mut impl Write, text: &str) -> io::Result<()> {
+        match self {
+            Charset::Encoding(e) if e == UTF_16LE => text
+                .encode_utf16()
+                .try_for_each(|u| out.write_all(&u.to_le_bytes())),
+            Charset::Encoding(e) if e == UTF_16BE => text
+                .encode_utf16()
+                .try_for_each(|u| out.write_all(&u.to_be_bytes())),
+            Charset::Encoding(_) => out.write_all(text.as_bytes()),
+            Charset::Utf32Le => text
+                .chars()
+                .try_for_each(|c| out.write_all(&(c as u32).to_le_bytes())),
+            Charset::Utf32Be => text
+                .chars()
+                .try_for_each(|c| out.write_all(&(c as u32).to_be_bytes())),
+        }
+    }
+
+    /// `text` in this charset, with `?` for each character it has no way to
+    /// write, and how many of those there were. Unicode charsets write all of
+    /// it.
+    pub fn encode(self, text: &str) -> (Vec<u8>, usize) {
+        let Charset::Encoding(e) = self else {
+            return (self.unicode(text), 0);
+        };
+        if e == UTF_8 || e == UTF_16LE || e == UTF_16BE {
+            return (self.unicode(text), 0);
+        }
+        let mut encoder = e.new_encoder();
+        let mut out = Vec::new();
+        let mut rest = text;
+        let mut unmappable = 0;
+        loop {
+            out.reserve(
+                encoder
+                    .max_buffer_length_from_utf8_without_replacement(rest.len())
+                    .unwrap_or(rest.len() * 4),
+            );
+            let (result, read) =
+                encoder.encode_from_utf8_to_vec_without_replacement(rest, &mut out, true);
+            rest = &rest[read..];
+            match result {
+                EncoderResult::InputEmpty => return (out, unmappable),
+                EncoderResult::OutputFull => {}
+                EncoderResult::Unmappable(_) => {
+                    unmappable += 1;
+                    out.push(b'?');
+                }
+            }
+        }
+    }
+
+    fn unicode(self, text: &str) -> Vec<u8> {
+        let mut out = Vec::with_capacity(text.len());
+        self.write(&mut out, text)
+            .expect("writing to a Vec does not fail");
+        out
+    }
+}
+
+/// Decodes a [`Charset`] a piece at a time.
+pub enum Decoder {
+    Encoding(encoding_rs::Decoder),
+    Utf32 { big_endian: bool },
+}
+
+impl Decoder {
+    /// Room `decode_to_string` may need for `len` more bytes.
+    pub fn max_utf8_buffer_length(&self, len: usize) -> usize {
+        match self {
+            Decoder::Encoding(d) => d.max_utf8_buffer_length(len).unwrap_or(len * 3),
+            // four bytes in, at most four out, or three for a lone U+FFFD
+            Decoder::Utf32 { .. } => len + 3,
+        }
+    }
+
+    /// Decode `bytes` onto `out`, `last` when they end the text. Returns the
+    /// bytes read, which may leave a split character for the next call, and
+    /// whether any were replaced with U+FFFD.
+    pub fn decode_to_string(
+        &mut self,
+        bytes: &[u8],
+        out: &mut String,
+        last: bool,
+    ) -> (usize, bool) {
+        match self {
+            Decoder::Encoding(d) => {
+                let (_, read, had_errors) = d.decode_to_string(bytes, out, last);
+                (read, had_errors)
+            }
+            Decoder::Utf32 { big_endian } => {
+                let mut units = bytes.chunks_exact(4);
+                let mut had_errors = false;
+                for unit in &mut units {
+                    let unit: [u8; 4] = unit.try_into().expect("4-byte unit");
+                    let code = if *big_endian {
+                        u32::from_be_bytes(unit)
+                    } else {
+                        u32::from_le_bytes(unit)
+                    };
+                    let c = char::from_u32(code).unwrap_or_else(|| {
+                        had_errors = true;
+                        char::REPLACEMENT_CHARACTER
+                    });
+                    out.push(c);
+                }
+                let rest = units.remainder().len();
+                if last && rest > 0 {
+                    out.push(char::REPLACEMENT_CHARACTER);
+                    return (bytes.len(), true);
+                }
+                (bytes.len() - rest, had_errors)
+            }
+        }
+    }
+}
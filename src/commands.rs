@@ -0,0 +1,40 @@
+use color_eyre::Result;
+
+use crate::analysis;
+use crate::args::{Command, NotesAction};
+use crate::convert;
+use crate::notes;
+use crate::stats;
+
+/// Run a subcommand instead of the reader.
+pub fn run(command: Command) -> Result<()> {
+    match command {
+        Command::Notes {
+            action:
+                NotesAction::Export {
+                    file_path,
+                    format,
+                    output,
+                },
+        } => notes::export(&file_path, format, output.as_deref()),
+        Command::Check { file_path } => analysis::check(&file_path),
+        Command::Stats { file_path } => stats::print(file_path.as_deref()),
+        Command::Convert {
+            to,
+            from,
+            dry_run,
+            keep_mtime,
+            lossy,
+            files,
+        } => convert::run(
+            &files,
+            from,
+            to,
+            &convert::Options {
+                dry_run,
+                keep_mtime,
+                lossy,
+            },
+        ),
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use color_eyre::Result;
+use color_eyre::eyre::WrapErr;
+use serde::{Deserialize, Serialize};
+
+use crate::autoscroll::AutoScrollConfig;
+use crate::theme::{ColorDepth, ThemeConfig};
+use crate::tts::TtsConfig;
+
+/// User configuration, read from `config.toml` in the noveltui config dir.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// name of the theme to start with
+    pub theme: Option<String>,
+    /// force a color depth instead of detecting it from the terminal
+    pub color_depth: Option<ColorDepth>,
+    /// user-defined themes, keyed by name
+    pub themes: BTreeMap<String, ThemeConfig>,
+    /// default side pane layout
+    pub layout: LayoutConfig,
+    /// capture mouse events (default true); `--no-mouse` overrides it
+    pub mouse: Option<bool>,
+    /// auto-scroll starting speed
+    pub autoscroll: AutoScrollConfig,
+    /// text-to-speech engine
+    pub tts: TtsConfig,
+    /// StarDict `.ifo` or MDict `.mdx` files used for word lookup, in order
+    pub dictionaries: Vec<PathBuf>,
+    /// leave chapters that repeat an earlier chapter word for word out of the TOC
+    pub hide_duplicate_chapters: bool,
+}
+
+/// Which side of the content the bookmark pane sits on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Left,
+    #[default]
+    Right,
+}
+
+/// Widths and placement of the side panes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LayoutConfig {
+    pub toc_width: u16,
+    pub bookmark_width: u16,
+    pub show_toc: bool,
+    pub bookmark_side: Side,
+    /// show each chapter's size in the TOC
+    pub toc_counts: bool,
+}
+
+impl LayoutConfig {
+    pub const MIN_PANE_WIDTH: u16 = 8;
+    pub const MAX_PANE_WIDTH: u16 = 80;
+
+    pub fn clamped(mut self) -> Self {
+        self.toc_width = self
+            .toc_width
+            .clamp(Self::MIN_PANE_WIDTH, Self::MAX_PANE_WIDTH);
+        self.bookmark_width = self
+            .bookmark_width
+            .clamp(Self::MIN_PANE_WIDTH, Self::MAX_PANE_WIDTH);
+        self
+    }
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self {
+            toc_width: 20,
+            bookmark_width: 20,
+            show_toc: true,
+            bookmark_side: Side::Right,
+            toc_counts: false,
+        }
+    }
+}
+
+/// Directory holding `config.toml`, e.g. `~/.config/noveltui`.
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|d| d.join("noveltui"))
+}
+
+impl Config {
+    /// Load the config from `path`, or from the default location when `None`.
+    /// A missing default config file is not an error.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let (path, explicit) = match path {
+            Some(p) => (p.to_path_buf(), true),
+            None => match config_dir() {
+                Some(dir) => (dir.join("config.toml"), false),
+                None => return Ok(Self::default()),
+            },
+        };
+        match fs::read_to_string(&path) {
+            Ok(s) => toml::from_str(&s)
+                .wrap_err_with(|| format!("Invalid config file {}", path.display())),
+            Err(e) if e.kind() == ErrorKind::NotFound && !explicit => Ok(Self::default()),
+            Err(e) => {
+                Err(e).wrap_err_with(|| format!("Cannot read config file {}", path.display()))
+            }
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use color_eyre::Result;
+use color_eyre::eyre::{WrapErr, eyre};
+
+use crate::args::Source;
+use crate::charset::Charset;
+use crate::loader::{self, Detection};
+use crate::state::BookState;
+
+/// How `convert` treats the books it is given.
+pub struct Options {
+    /// only report what would happen
+    pub dry_run: bool,
+    pub keep_mtime: bool,
+    /// write books that lose characters on the way
+    pub lossy: bool,
+}
+
+// what became of one book
+enum Outcome {
+    Converted,
+    Unchanged,
+    Skipped,
+}
+
+/// Rewrite each of `files` in `to`, reading it in `from`, and report how
+/// each one went. Books that fail are reported and passed over.
+pub fn run(files: &[impl AsRef<Path>], from: Source, to: Charset, options: &Options) -> Result<()> {
+    let (mut converted, mut unchanged, mut skipped, mut failed) = (0, 0, 0, 0);
+    for path in files {
+        let path = path.as_ref();
+        match convert(path, from, to, options) {
+            Ok(Outcome::Converted) => converted += 1,
+            Ok(Outcome::Unchanged) => unchanged += 1,
+            Ok(Outcome::Skipped) => skipped += 1,
+            Err(e) => {
+                failed += 1;
+                eprintln!("{}: {:#}", path.display(), e);
+            }
+        }
+    }
+    println!(
+        "{} {}, {} already {}, {} skipped, {} failed",
+        converted,
+        if options.dry_run {
+            "to convert"
+        } else {
+            "converted"
+        },
+        unchanged,
+        to.name(),
+        skipped,
+        failed
+    );
+    if failed > 0 {
+        return Err(eyre!(
+            "{} of {} books could not be converted",
+            failed,
+            files.len()
+        ));
+    }
+    Ok(())
+}
+
+fn convert(path: &Path, from: Source, to: Charset, options: &Options) -> Result<Outcome> {
+    let bytes = fs::read(path).wrap_err("could not read the book")?;
+    let mut state = BookState::load(path);
+    // a charset picked in the reader is what the book is read in there too
+    let picked = state.encoding();
+    let given = match from {
+        Source::Auto => picked,
+        Source::Named(charset) => Some(charset),
+    };
+    let (charset, bom, how) = loader::sniff(&bytes, given);
+    let how = match how {
+        Detection::Given if matches!(from, Source::Auto) => "picked in the reader",
+        Detection::Given => "given",
+        Detection::Bom => "byte order mark",
+        Detection::Utf8 => "valid UTF-8",
+        Detection::Guessed { confident: true } => "guessed",
+        Detection::Guessed { confident: false } => "guessed, likely wrong",
+    };
+    let (text, unreadable) = loader::decode(&bytes[bom..], charset, &mut |_, _| {});
+    let (mut out, unwritable) = to.encode(&text);
+    // the reader only tells UTF-16 and UTF-32 from other text by their mark;
+    // UTF-8 goes without one, as noveltui writes books
+    if to != Charset::default() {
+        out.splice(0..0, to.bom().iter().copied());
+    }
+
+    let mut report = format!(
+        "{}: {} ({}) → {}",
+        path.display(),
+        charset.name(),
+        how,
+        to.name()
+    );
+    if unreadable > 0 {
+        report += &format!(", {} characters unreadable", unreadable);
+    }
+    if unwritable > 0 {
+        report += &format!(", {} characters not in {}", unwritable, to.name());
+    }
+    if out == bytes {
+        println!("{}, unchanged", report);
+        return Ok(Outcome::Unchanged);
+    }
+    if (unreadable > 0 || unwritable > 0) && !options.lossy {
+        println!("{}, skipped; pass --lossy to convert it anyway", report);
+        return Ok(Outcome::Skipped);
+    }
+    if options.dry_run {
+        println!("{}, would convert", report);
+        return Ok(Outcome::Converted);
+    }
+
+    loader::replace_file(path, options.keep_mtime, |file| file.write_all(&out))
+        .wrap_err("could not write the book")?;
+    if picked.is_some() {
+        // the picked charset no longer fits the file
+        state.encoding = None;
+        state.save(path)?;
+    }
+    println!("{}, converted", report);
+    Ok(Outcome::Converted)
+}
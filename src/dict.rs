@@ -0,0 +1,874 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use color_eyre::Result;
+use color_eyre::eyre::{WrapErr, eyre};
+use encoding_rs::{Encoding, UTF_16LE};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::text::is_cjk;
+
+/// Longest CJK segment tried when matching against the dictionaries.
+const MAX_SEGMENT_CHARS: usize = 8;
+
+/// A local dictionary file.
+#[derive(Debug)]
+pub struct Dictionary {
+    pub name: String,
+    kind: DictKind,
+}
+
+#[derive(Debug)]
+enum DictKind {
+    StarDict(StarDict),
+    MDict(MDict),
+}
+
+impl Dictionary {
+    /// Open a StarDict `.ifo` or an MDict `.mdx` file.
+    pub fn open(path: &Path) -> Result<Self> {
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        let (name, kind) = match ext.as_str() {
+            "ifo" => {
+                let sd = StarDict::open(path)?;
+                (sd.name.clone(), DictKind::StarDict(sd))
+            }
+            "mdx" => {
+                let md = MDict::open(path)?;
+                (md.name.clone(), DictKind::MDict(md))
+            }
+            _ => return Err(eyre!("{}: not a .ifo or .mdx file", path.display())),
+        };
+        Ok(Self { name, kind })
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        let key = word.to_lowercase();
+        match &self.kind {
+            DictKind::StarDict(sd) => sd.index.contains_key(&key),
+            DictKind::MDict(md) => md.index.contains_key(&key),
+        }
+    }
+
+    /// All definitions of `word`, as plain text.
+    pub fn lookup(&self, word: &str) -> Result<Vec<String>> {
+        match &self.kind {
+            DictKind::StarDict(sd) => sd.lookup(word),
+            DictKind::MDict(md) => md.lookup(word),
+        }
+    }
+}
+
+/// The dictionaries listed in the config, in order.
+#[derive(Debug, Default)]
+pub struct Dictionaries {
+    pub dicts: Vec<Dictionary>,
+}
+
+impl Dictionaries {
+    /// Open every dictionary in `paths`; the ones that fail are returned as errors
+    /// alongside the ones that opened.
+    pub fn open(paths: &[PathBuf]) -> (Self, Vec<color_eyre::Report>) {
+        let mut dicts = Vec::new();
+        let mut errors = Vec::new();
+        for path in paths {
+            match Dictionary::open(&expand_home(path)) {
+                Ok(d) => dicts.push(d),
+                Err(e) => errors.push(e),
+            }
+        }
+        (Self { dicts }, errors)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dicts.is_empty()
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        self.dicts.iter().any(|d| d.contains(word))
+    }
+
+    /// Number of chars in the longest dictionary word at the start of `chars`.
+    /// Unknown text still gives a segment of one char.
+    pub fn longest_match(&self, chars: &[char]) -> usize {
+        (2..=chars.len().min(MAX_SEGMENT_CHARS))
+            .rev()
+            .find(|&n| self.contains(&chars[..n].iter().collect::<String>()))
+            .unwrap_or(1)
+    }
+
+    /// Definitions of `word` from each dictionary that has it, by dictionary name.
+    pub fn lookup(&self, word: &str) -> Vec<(String, Result<Vec<String>>)> {
+        self.dicts
+            .iter()
+            .filter(|d| d.contains(word))
+            .map(|d| (d.name.clone(), d.lookup(word)))
+            .collect()
+    }
+}
+
+/// Words that can be looked up in `line`, in order: runs of letters for
+/// alphabetic scripts, and for CJK text the longest dictionary word at each
+/// point (greedy forward matching, one char when nothing matches).
+pub fn lookup_targets(line: &str, dicts: &Dictionaries) -> Vec<Range<usize>> {
+    let chars: Vec<(usize, char)> = line.char_indices().collect();
+    let end_of = |i: usize| chars.get(i).map_or(line.len(), |(b, _)| *b);
+    let mut targets = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i].1;
+        if is_cjk(c) {
+            let run: Vec<char> = chars[i..]
+                .iter()
+                .take(MAX_SEGMENT_CHARS)
+                .map(|(_, c)| *c)
+                .take_while(|c| is_cjk(*c))
+                .collect();
+            let n = dicts.longest_match(&run);
+            targets.push(chars[i].0..end_of(i + n));
+            i += n;
+        } else if c.is_alphanumeric() {
+            let start = i;
+            // keep "don't" and "well-known" together
+            while i < chars.len()
+                && !is_cjk(chars[i].1)
+                && (chars[i].1.is_alphanumeric()
+                    || (matches!(chars[i].1, '\'' | '’' | '-')
+                        && chars.get(i + 1).is_some_and(|(_, n)| n.is_alphanumeric())))
+            {
+                i += 1;
+            }
+            targets.push(chars[start].0..end_of(i));
+        } else {
+            i += 1;
+        }
+    }
+    targets
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// StarDict: .ifo (metadata) + .idx (sorted word index) + .dict[.dz] (data)
+
+#[derive(Debug)]
+struct StarDict {
+    name: String,
+    same_type_sequence: Option<String>,
+    data: DictData,
+    // lowercased word -> (offset, size) in the data file
+    index: HashMap<String, Vec<(u64, u32)>>,
+}
+
+#[derive(Debug)]
+enum DictData {
+    File(PathBuf),
+    // .dict.dz is gzip-compatible; it is inflated once when opened
+    Memory(Vec<u8>),
+}
+
+impl StarDict {
+    fn open(ifo: &Path) -> Result<Self> {
+        let info = fs::read_to_string(ifo).wrap_err_with(|| format!("{}", ifo.display()))?;
+        let field = |key: &str| {
+            info.lines()
+                .find_map(|l| l.strip_prefix(key)?.strip_prefix('='))
+                .map(|v| v.trim().to_string())
+        };
+        let name = field("bookname").unwrap_or_else(|| stem_name(ifo));
+        let offset_bits_64 = field("idxoffsetbits").as_deref() == Some("64");
+        let same_type_sequence = field("sametypesequence");
+
+        let idx_bytes = match read_sibling(ifo, "idx")? {
+            Some(bytes) => bytes,
+            None => {
+                let gz = read_sibling(ifo, "idx.gz")?
+                    .ok_or_else(|| eyre!("{}: missing .idx file", ifo.display()))?;
+                let mut out = Vec::new();
+                GzDecoder::new(&gz[..]).read_to_end(&mut out)?;
+                out
+            }
+        };
+        let index = parse_star_index(&idx_bytes, offset_bits_64)
+            .ok_or_else(|| eyre!("{}: corrupt .idx file", ifo.display()))?;
+
+        let dict_path = ifo.with_extension("dict");
+        let data = if dict_path.exists() {
+            DictData::File(dict_path)
+        } else {
+            let dz = ifo.with_extension("dict.dz");
+            let compressed =
+                fs::read(&dz).wrap_err_with(|| format!("{}: missing .dict file", ifo.display()))?;
+            let mut out = Vec::new();
+            GzDecoder::new(&compressed[..]).read_to_end(&mut out)?;
+            DictData::Memory(out)
+        };
+
+        Ok(Self {
+            name,
+            same_type_sequence,
+            data,
+            index,
+        })
+    }
+
+    fn lookup(&self, word: &str) -> Result<Vec<String>> {
+        let Some(entries) = self.index.get(&word.to_lowercase()) else {
+            return Ok(Vec::new());
+        };
+        let mut out = Vec::new();
+        let outside = || eyre!("entry outside of dictionary data");
+        for &(offset, size) in entries {
+            let end = offset.checked_add(size as u64).ok_or_else(outside)?;
+            let raw = match &self.data {
+                DictData::File(path) => {
+                    let mut f = File::open(path)?;
+                    // checked first, so a corrupt size cannot ask for more
+                    // memory than the file holds
+                    if end > f.metadata()?.len() {
+                        return Err(outside());
+                    }
+                    f.seek(SeekFrom::Start(offset))?;
+                    let mut buf = vec![0; size as usize];
+                    f.read_exact(&mut buf)?;
+                    buf
+                }
+                DictData::Memory(data) => usize::try_from(offset)
+                    .ok()
+                    .zip(usize::try_from(end).ok())
+                    .and_then(|(offset, end)| data.get(offset..end))
+                    .ok_or_else(outside)?
+                    .to_vec(),
+            };
+            out.push(star_fields_to_text(
+                &raw,
+                self.same_type_sequence.as_deref(),
+            ));
+        }
+        Ok(out)
+    }
+}
+
+fn read_sibling(ifo: &Path, ext: &str) -> Result<Option<Vec<u8>>> {
+    let path = ifo.with_extension(ext);
+    match fs::read(&path) {
+        Ok(b) => Ok(Some(b)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).wrap_err_with(|| format!("{}", path.display())),
+    }
+}
+
+fn stem_name(path: &Path) -> String {
+    path.file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("dictionary")
+        .to_string()
+}
+
+fn parse_star_index(
+    bytes: &[u8],
+    offset_bits_64: bool,
+) -> Option<HashMap<String, Vec<(u64, u32)>>> {
+    let mut index: HashMap<String, Vec<(u64, u32)>> = HashMap::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let end = pos + bytes[pos..].iter().position(|b| *b == 0)?;
+        let word = String::from_utf8_lossy(&bytes[pos..end]).to_lowercase();
+        pos = end + 1;
+        let offset = if offset_bits_64 {
+            let v = u64::from_be_bytes(bytes.get(pos..pos + 8)?.try_into().ok()?);
+            pos += 8;
+            v
+        } else {
+            let v = u32::from_be_bytes(bytes.get(pos..pos + 4)?.try_into().ok()?);
+            pos += 4;
+            v as u64
+        };
+        let size = u32::from_be_bytes(bytes.get(pos..pos + 4)?.try_into().ok()?);
+        pos += 4;
+        index.entry(word).or_default().push((offset, size));
+    }
+    Some(index)
+}
+
+// An entry is a list of typed fields. With `sametypesequence` the type chars are
+// given once in the .ifo and the last field runs to the end of the entry.
+fn star_fields_to_text(raw: &[u8], same_type_sequence: Option<&str>) -> String {
+    let mut parts = Vec::new();
+    let mut pos = 0;
+    let mut types = same_type_sequence.map(|s| s.chars().collect::<Vec<_>>());
+    let mut field = 0;
+    while pos < raw.len() {
+        let ty = match &mut types {
+            Some(seq) => match seq.get(field) {
+                Some(t) => *t,
+                None => break,
+            },
+            None => {
+                let t = raw[pos] as char;
+                pos += 1;
+                t
+            }
+        };
+        let is_last = types.as_ref().is_some_and(|seq| field + 1 == seq.len());
+        field += 1;
+        let data = if ty.is_ascii_uppercase() {
+            // binary field: resource, image, ... skip it
+            let Some(size) = raw.get(pos..pos + 4) else {
+                break;
+            };
+            let size = u32::from_be_bytes(size.try_into().unwrap_or_default()) as usize;
+            pos = pos.saturating_add(4 + size);
+            continue;
+        } else if is_last {
+            let d = &raw[pos..];
+            pos = raw.len();
+            d
+        } else {
+            let end = raw[pos..]
+                .iter()
+                .position(|b| *b == 0)
+                .map_or(raw.len(), |p| pos + p);
+            let d = &raw[pos..end];
+            pos = end + 1;
+            d
+        };
+        let text = String::from_utf8_lossy(data);
+        match ty {
+            'h' | 'g' | 'x' => parts.push(strip_markup(&text)),
+            'm' | 't' | 'y' | 'l' | 'k' | 'w' => parts.push(text.trim().to_string()),
+            _ => {}
+        }
+    }
+    parts.join("\n")
+}
+
+// ---------------------------------------------------------------------------
+// MDict (.mdx), format version 1.2 and 2.0 without encryption
+
+#[derive(Debug)]
+struct MDict {
+    name: String,
+    path: PathBuf,
+    encoding: &'static Encoding,
+    // lowercased headword -> positions in `keys`
+    index: HashMap<String, Vec<usize>>,
+    // record offset of every headword, in file order
+    offsets: Vec<u64>,
+    // (compressed size, decompressed size) of every record block
+    record_blocks: Vec<(u64, u64)>,
+    // file offset of the first record block
+    records_start: u64,
+    records_total: u64,
+}
+
+struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+    wide: bool,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(data: &'a [u8], wide: bool) -> Self {
+        Self { data, pos: 0, wide }
+    }
+
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let end = self.pos.checked_add(n)?;
+        let s = self.data.get(self.pos..end)?;
+        self.pos = end;
+        Some(s)
+    }
+
+    // a size read from the file, which must fit in what is left of it
+    fn size(&mut self) -> Option<usize> {
+        let n = usize::try_from(self.number()?).ok()?;
+        (n <= self.data.len() - self.pos.min(self.data.len())).then_some(n)
+    }
+
+    // MDict numbers are 8 bytes from version 2.0 on, 4 bytes before
+    fn number(&mut self) -> Option<u64> {
+        if self.wide {
+            Some(u64::from_be_bytes(self.take(8)?.try_into().ok()?))
+        } else {
+            Some(u32::from_be_bytes(self.take(4)?.try_into().ok()?) as u64)
+        }
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_be_bytes(self.take(2)?.try_into().ok()?))
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+}
+
+impl MDict {
+    fn open(path: &Path) -> Result<Self> {
+        let data = fs::read(path).wrap_err_with(|| format!("{}", path.display()))?;
+        Self::parse(path, &data)
+            .ok_or_else(|| eyre!("{}: unsupported or corrupt MDict file", path.display()))?
+    }
+
+    fn parse(path: &Path, data: &[u8]) -> Option<Result<Self>> {
+        let header_len = u32::from_be_bytes(data.get(0..4)?.try_into().ok()?) as usize;
+        let (header, _, _) = UTF_16LE.decode(data.get(4..4usize.checked_add(header_len)?)?);
+        let attr = |name: &str| {
+            Regex::new(&format!(r#"{}="([^"]*)""#, name))
+                .ok()?
+                .captures(&header)
+                .map(|c| c[1].to_string())
+        };
+        let version: f32 = attr("GeneratedByEngineVersion")?.parse().ok()?;
+        let encrypted: u32 = attr("Encrypted").and_then(|e| e.parse().ok()).unwrap_or(0);
+        if encrypted != 0 {
+            return Some(Err(eyre!(
+                "{}: encrypted MDict files are not supported",
+                path.display()
+            )));
+        }
+        let encoding = match attr("Encoding").as_deref() {
+            None | Some("") => encoding_rs::UTF_8,
+            Some(label) if label.eq_ignore_ascii_case("UTF-16") => UTF_16LE,
+            Some(label) => Encoding::for_label(label.as_bytes()).unwrap_or(encoding_rs::UTF_8),
+        };
+        let utf16 = encoding == UTF_16LE;
+        let name = attr("Title")
+            .filter(|t| !t.is_empty() && t != "Title (No HTML code allowed)")
+            .unwrap_or_else(|| stem_name(path));
+        let wide = version >= 2.0;
+
+        // keyword section
+        let mut c = Cursor::new(data, wide);
+        c.pos = 4 + header_len + 4;
+        let num_key_blocks = c.number()?;
+        let _num_entries = c.number()?;
+        let key_info_decompressed = if wide { c.number()? } else { 0 };
+        let key_info_size = c.size()?;
+        let key_blocks_size = c.size()?;
+        if wide {
+            c.take(4)?; // adler32 of the numbers above
+        }
+        let key_info_raw = c.take(key_info_size)?;
+        let key_info = if wide {
+            decompress_block(key_info_raw, key_info_decompressed)?
+        } else {
+            key_info_raw.to_vec()
+        };
+        let key_blocks_start = c.pos;
+        c.take(key_blocks_size)?;
+
+        // sizes of each key block, from the key info
+        let unit = if utf16 { 2 } else { 1 };
+        let mut info = Cursor::new(&key_info, wide);
+        let mut key_block_sizes = Vec::new();
+        for _ in 0..num_key_blocks {
+            let _entries = info.number()?;
+            for _ in 0..2 {
+                // first and last headword of the block
+                let len = if wide {
+                    info.u16()? as usize
+                } else {
+                    info.u8()? as usize
+                };
+                let terminator = if wide { unit } else { 0 };
+                info.take(len * unit + terminator)?;
+            }
+            let comp = usize::try_from(info.number()?).ok()?;
+            let decomp = info.number()?;
+            key_block_sizes.push((comp, decomp));
+        }
+
+        let mut index: HashMap<String, Vec<usize>> = HashMap::new();
+        let mut offsets = Vec::new();
+        let mut pos = key_blocks_start;
+        for (size, decomp) in key_block_sizes {
+            let end = pos.checked_add(size)?;
+            let block = decompress_block(data.get(pos..end)?, decomp)?;
+            pos = end;
+            let mut k = Cursor::new(&block, wide);
+            while k.pos < block.len() {
+                let offset = k.number()?;
+                let rest = &block[k.pos..];
+                let end = if utf16 {
+                    rest.chunks(2).position(|ch| ch == [0, 0])? * 2
+                } else {
+                    rest.iter().position(|b| *b == 0)?
+                };
+                let (word, _) = encoding.decode_without_bom_handling(&rest[..end]);
+                k.pos += end + unit;
+                index
+                    .entry(word.trim().to_lowercase())
+                    .or_default()
+                    .push(offsets.len());
+                offsets.push(offset);
+            }
+        }
+
+        // record section
+        let mut r = Cursor::new(data, wide);
+        r.pos = key_blocks_start + key_blocks_size;
+        let num_record_blocks = r.number()?;
+        let _num_entries = r.number()?;
+        let _record_info_size = r.number()?;
+        let _record_blocks_size = r.number()?;
+        let mut record_blocks = Vec::new();
+        let mut records_total = 0u64;
+        let mut records_size = 0u64;
+        for _ in 0..num_record_blocks {
+            let comp = r.number()?;
+            let decomp = r.number()?;
+            records_total = records_total.checked_add(decomp)?;
+            records_size = records_size.checked_add(comp)?;
+            record_blocks.push((comp, decomp));
+        }
+        // every block must be in the file, so reading one never asks for more
+        if records_size > (data.len() - r.pos.min(data.len())) as u64 {
+            return None;
+        }
+
+        Some(Ok(Self {
+            name,
+            path: path.to_path_buf(),
+            encoding,
+            index,
+            offsets,
+            record_blocks,
+            records_start: r.pos as u64,
+            records_total,
+        }))
+    }
+
+    fn lookup(&self, word: &str) -> Result<Vec<String>> {
+        let mut out = Vec::new();
+        let mut file = File::open(&self.path)?;
+        for &i in self.index.get(&word.to_lowercase()).into_iter().flatten() {
+            let text = self.record(&mut file, i)?;
+            // "@@@LINK=other" entries point at another headword
+            if let Some(target) = text.trim().strip_prefix("@@@LINK=") {
+                if let Some(&j) = self
+                    .index
+                    .get(&target.trim().to_lowercase())
+                    .and_then(|v| v.first())
+                {
+                    out.push(strip_markup(&self.record(&mut file, j)?));
+                }
+                continue;
+            }
+            out.push(strip_markup(&text));
+        }
+        Ok(out)
+    }
+
+    fn record(&self, file: &mut File, i: usize) -> Result<String> {
+        let start = self.offsets[i];
+        let end = self
+            .offsets
+            .iter()
+            .skip(i + 1)
+            .find(|&&o| o > start)
+            .copied()
+            .unwrap_or(self.records_total);
+        let outside = || eyre!("record outside of dictionary data");
+
+        // find the block holding `start`
+        let mut file_pos = self.records_start;
+        let mut block_start = 0u64;
+        for &(comp, decomp) in &self.record_blocks {
+            let block_end = block_start.checked_add(decomp).ok_or_else(outside)?;
+            if start < block_end {
+                file.seek(SeekFrom::Start(file_pos))?;
+                // the block sizes were checked against the file when it was
+                // opened; reading through `take` holds even if it shrank since
+                let mut raw = Vec::new();
+                file.take(comp).read_to_end(&mut raw)?;
+                if raw.len() as u64 != comp {
+                    return Err(outside());
+                }
+                let block = decompress_block(&raw, decomp)
+                    .ok_or_else(|| eyre!("unsupported MDict record compression"))?;
+                let from = (start - block_start) as usize;
+                let to = end.checked_sub(block_start).ok_or_else(outside)?;
+                let to = usize::try_from(to).unwrap_or(usize::MAX).min(block.len());
+                let bytes = block.get(from..to).unwrap_or_default();
+                let (text, _) = self.encoding.decode_without_bom_handling(bytes);
+                return Ok(text.trim_end_matches('\0').to_string());
+            }
+            file_pos = file_pos.checked_add(comp).ok_or_else(outside)?;
+            block_start = block_end;
+        }
+        Err(outside())
+    }
+}
+
+// compressed MDict blocks start with a 4-byte type and a 4-byte checksum;
+// they inflate to no more than the `size` the file gives for them
+fn decompress_block(raw: &[u8], size: u64) -> Option<Vec<u8>> {
+    let kind = u32::from_le_bytes(raw.get(0..4)?.try_into().ok()?);
+    let body = raw.get(8..)?;
+    match kind {
+        0 => Some(body.to_vec()),
+        2 => {
+            let mut out = Vec::new();
+            ZlibDecoder::new(body)
+                .take(size)
+                .read_to_end(&mut out)
+                .ok()?;
+            Some(out)
+        }
+        // 1 is LZO, which is rare and not supported
+        _ => None,
+    }
+}
+
+/// Turn an HTML or XDXF definition into plain text.
+pub fn strip_markup(html: &str) -> String {
+    static BREAKS: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"(?i)<\s*(br|/p|/div|/li|/h\d)\s*/?>").unwrap());
+    static TAGS: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)<[^>]*>").unwrap());
+    static BLANKS: Lazy<Regex> = Lazy::new(|| Regex::new(r"\n\s*\n\s*\n+").unwrap());
+
+    let text = BREAKS.replace_all(html, "\n");
+    let text = TAGS.replace_all(&text, "");
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    BLANKS.replace_all(text.trim(), "\n\n").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::Compression;
+    use flate2::write::ZlibEncoder;
+
+    use super::*;
+
+    // a fresh directory for one test's files
+    fn scratch(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("noveltui-dict-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // .ifo, .idx and .dict of a StarDict with one `m` field per entry; `size`
+    // overrides the size the index gives the last entry
+    fn stardict(dir: &Path, entries: &[(&str, &str)], size: Option<u32>) -> PathBuf {
+        let ifo = dir.join("test.ifo");
+        fs::write(
+            &ifo,
+            format!(
+                "StarDict's dict ifo file\nversion=2.4.2\nbookname=Test\nwordcount={}\nsametypesequence=m\n",
+                entries.len()
+            ),
+        )
+        .unwrap();
+        let (mut idx, mut dict) = (Vec::new(), Vec::new());
+        for (i, (word, text)) in entries.iter().enumerate() {
+            idx.extend_from_slice(word.as_bytes());
+            idx.push(0);
+            idx.extend_from_slice(&(dict.len() as u32).to_be_bytes());
+            let len = match size {
+                Some(size) if i + 1 == entries.len() => size,
+                _ => text.len() as u32,
+            };
+            idx.extend_from_slice(&len.to_be_bytes());
+            dict.extend_from_slice(text.as_bytes());
+        }
+        fs::write(dir.join("test.idx"), idx).unwrap();
+        fs::write(dir.join("test.dict"), dict).unwrap();
+        ifo
+    }
+
+    #[test]
+    fn stardict_looks_up_words() {
+        let dir = scratch("stardict");
+        let ifo = stardict(
+            &dir,
+            &[("Apple", "a fruit"), ("苹果", "<b>apple</b>")],
+            None,
+        );
+        let dict = Dictionary::open(&ifo).unwrap();
+        assert_eq!(dict.name, "Test");
+        assert!(dict.contains("apple"));
+        assert_eq!(dict.lookup("APPLE").unwrap(), ["a fruit"]);
+        assert_eq!(dict.lookup("苹果").unwrap(), ["<b>apple</b>"]);
+        assert!(dict.lookup("pear").unwrap().is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn stardict_entry_past_the_data_is_an_error() {
+        let dir = scratch("stardict-past");
+        let ifo = stardict(&dir, &[("a", "x"), ("b", "y")], Some(u32::MAX));
+        let dict = Dictionary::open(&ifo).unwrap();
+        assert_eq!(dict.lookup("a").unwrap(), ["x"]);
+        assert!(dict.lookup("b").is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn stardict_truncated_index_is_corrupt() {
+        let dir = scratch("stardict-truncated");
+        let ifo = stardict(&dir, &[("word", "text")], None);
+        let idx = fs::read(dir.join("test.idx")).unwrap();
+        fs::write(dir.join("test.idx"), &idx[..idx.len() - 3]).unwrap();
+        let err = Dictionary::open(&ifo).unwrap_err();
+        assert!(err.to_string().contains("corrupt .idx"), "{}", err);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn star_fields_skip_binary_fields() {
+        let mut raw = b"mplain\0".to_vec();
+        raw.push(b'P');
+        raw.extend_from_slice(&3u32.to_be_bytes());
+        raw.extend_from_slice(b"png");
+        raw.extend_from_slice(b"h<i>html</i>\0");
+        assert_eq!(star_fields_to_text(&raw, None), "plain\nhtml");
+        // a binary field claiming more than there is ends the entry
+        let mut raw = b"mtext\0W".to_vec();
+        raw.extend_from_slice(&u32::MAX.to_be_bytes());
+        assert_eq!(star_fields_to_text(&raw, None), "text");
+    }
+
+    fn block(kind: u32, body: &[u8]) -> Vec<u8> {
+        let mut out = kind.to_le_bytes().to_vec();
+        out.extend_from_slice(&[0; 4]); // checksum, not checked
+        match kind {
+            2 => {
+                let mut z = ZlibEncoder::new(Vec::new(), Compression::default());
+                z.write_all(body).unwrap();
+                out.extend(z.finish().unwrap());
+            }
+            _ => out.extend_from_slice(body),
+        }
+        out
+    }
+
+    // a version 2.0 MDict with one key block and one zlib record block;
+    // `sizes` overrides the record block's compressed and decompressed sizes
+    fn mdx(entries: &[(&str, &str)], sizes: Option<(u64, u64)>) -> Vec<u8> {
+        let header: Vec<u8> = r#"<Dictionary GeneratedByEngineVersion="2.0" Encrypted="0" Encoding="UTF-8" Title="Test"/>"#
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let mut out = (header.len() as u32).to_be_bytes().to_vec();
+        out.extend(header);
+        out.extend_from_slice(&[0; 4]);
+
+        let (mut keys, mut records) = (Vec::new(), Vec::new());
+        for (word, text) in entries {
+            keys.extend_from_slice(&(records.len() as u64).to_be_bytes());
+            keys.extend_from_slice(word.as_bytes());
+            keys.push(0);
+            records.extend_from_slice(text.as_bytes());
+            records.push(0);
+        }
+        let key_block = block(0, &keys);
+        let mut info = (entries.len() as u64).to_be_bytes().to_vec();
+        for word in [entries[0].0, entries[entries.len() - 1].0] {
+            info.extend_from_slice(&(word.len() as u16).to_be_bytes());
+            info.extend_from_slice(word.as_bytes());
+            info.push(0);
+        }
+        info.extend_from_slice(&(key_block.len() as u64).to_be_bytes());
+        info.extend_from_slice(&(keys.len() as u64).to_be_bytes());
+        let key_info = block(2, &info);
+        for n in [
+            1,
+            entries.len(),
+            info.len(),
+            key_info.len(),
+            key_block.len(),
+        ] {
+            out.extend_from_slice(&(n as u64).to_be_bytes());
+        }
+        out.extend_from_slice(&[0; 4]);
+        out.extend(key_info);
+        out.extend(key_block);
+
+        let record_block = block(2, &records);
+        let (comp, decomp) = sizes.unwrap_or((record_block.len() as u64, records.len() as u64));
+        for n in [1, entries.len() as u64, 16, comp, comp, decomp] {
+            out.extend_from_slice(&n.to_be_bytes());
+        }
+        out.extend(record_block);
+        out
+    }
+
+    fn open_mdx(name: &str, data: &[u8]) -> (PathBuf, Result<Dictionary>) {
+        let dir = scratch(name);
+        let path = dir.join("test.mdx");
+        fs::write(&path, data).unwrap();
+        let dict = Dictionary::open(&path);
+        (dir, dict)
+    }
+
+    #[test]
+    fn mdict_looks_up_words_and_links() {
+        let entries = [
+            ("color", "<p>a hue</p>"),
+            ("colour", "@@@LINK=color"),
+            ("颜色", "color"),
+        ];
+        let (dir, dict) = open_mdx("mdict", &mdx(&entries, None));
+        let dict = dict.unwrap();
+        assert_eq!(dict.name, "Test");
+        assert_eq!(dict.lookup("Color").unwrap(), ["a hue"]);
+        assert_eq!(dict.lookup("colour").unwrap(), ["a hue"]);
+        assert_eq!(dict.lookup("颜色").unwrap(), ["color"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn mdict_truncated_anywhere_fails_to_open() {
+        let data = mdx(&[("a", "one"), ("b", "two")], None);
+        for len in 0..data.len() {
+            assert!(MDict::parse(Path::new("test.mdx"), &data[..len]).is_none());
+        }
+    }
+
+    #[test]
+    fn mdict_block_larger_than_the_file_fails_to_open() {
+        let data = mdx(&[("a", "one")], Some((u64::MAX, 4)));
+        let (dir, dict) = open_mdx("mdict-huge", &data);
+        assert!(dict.is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn mdict_record_past_the_blocks_is_an_error() {
+        // the block claims less text than the second record starts at
+        let entries = [("a", "one"), ("b", "two")];
+        let comp = block(2, b"one\0two\0").len() as u64;
+        let (dir, dict) = open_mdx("mdict-short", &mdx(&entries, Some((comp, 2))));
+        let dict = dict.unwrap();
+        // the block inflates no further than it says
+        assert_eq!(dict.lookup("a").unwrap(), ["on"]);
+        assert!(dict.lookup("b").is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,49 @@
+use std::time::{Duration, Instant};
+
+use color_eyre::Result;
+use crossterm::event::{self, Event};
+
+/// How often a `Tick` is sent.
+pub const TICK_RATE: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AppEvent {
+    /// anything crossterm reports: keys, mouse, resize, focus, paste
+    Terminal(Event),
+    /// periodic wake-up for timed behaviour
+    Tick,
+}
+
+/// Polls the terminal with a timeout so a `Tick` goes out every `TICK_RATE`,
+/// however busy the terminal is.
+#[derive(Debug)]
+pub struct EventLoop {
+    tick_rate: Duration,
+    last_tick: Instant,
+}
+
+impl Default for EventLoop {
+    fn default() -> Self {
+        Self::new(TICK_RATE)
+    }
+}
+
+impl EventLoop {
+    pub fn new(tick_rate: Duration) -> Self {
+        Self {
+            tick_rate,
+            last_tick: Instant::now(),
+        }
+    }
+
+    pub fn next_event(&mut self) -> Result<AppEvent> {
+        // a tick that is due goes first, so a steady stream of events (like
+        // mouse motion) cannot hold timed behaviour back
+        let elapsed = self.last_tick.elapsed();
+        if elapsed < self.tick_rate && event::poll(self.tick_rate - elapsed)? {
+            return Ok(AppEvent::Terminal(event::read()?));
+        }
+        self.last_tick = Instant::now();
+        Ok(AppEvent::Tick)
+    }
+}
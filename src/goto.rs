@@ -0,0 +1,139 @@
+use crate::chapter::Chapter;
+
+/// Where a `:` command asks to go.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    /// 1-based line in the whole file
+    Line(usize),
+    Percent(f64),
+    /// a `Chapter::number`, as written in the heading
+    Chapter(usize),
+    /// part of a chapter title
+    Title(String),
+}
+
+/// Why line 0 is not a line, for `:0` and `--line 0` alike.
+pub const NO_LINE_ZERO: &str = "Lines are numbered from 1";
+
+/// Parse `123`, `45%`, `ch 300` or a title substring. Empty input is no
+/// target; line 0 is an error.
+pub fn parse(input: &str) -> Result<Option<Target>, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(None);
+    }
+    if let Ok(line) = input.parse() {
+        if line == 0 {
+            return Err(NO_LINE_ZERO.to_string());
+        }
+        return Ok(Some(Target::Line(line)));
+    }
+    if let Some(p) = input.strip_suffix('%')
+        && let Ok(p) = p.trim().parse::<f64>()
+    {
+        return Ok(Some(Target::Percent(p)));
+    }
+    let lower = input.to_lowercase();
+    for prefix in ["chapter", "ch"] {
+        if let Some(rest) = lower.strip_prefix(prefix)
+            && let Ok(n) = rest.trim().parse()
+        {
+            return Ok(Some(Target::Chapter(n)));
+        }
+    }
+    Ok(Some(Target::Title(input.to_string())))
+}
+
+/// 0-based global line for `target`, or a message saying why there is none.
+pub fn resolve(target: &Target, chapters: &[Chapter], total_lines: usize) -> Result<usize, String> {
+    if total_lines == 0 {
+        return Err("The book is empty".to_string());
+    }
+    match target {
+        Target::Line(line) => {
+            if *line > total_lines {
+                Err(format!(
+                    "Line {} is past the end ({} lines)",
+                    line, total_lines
+                ))
+            } else {
+                Ok(line.saturating_sub(1))
+            }
+        }
+        Target::Percent(p) => {
+            if !(0.0..=100.0).contains(p) {
+                return Err(format!("{}% is not between 0% and 100%", p));
+            }
+            let line = (total_lines as f64 * p / 100.0) as usize;
+            Ok(line.min(total_lines - 1))
+        }
+        Target::Chapter(n) => chapters
+            .iter()
+            .find(|c| c.number == *n && !c.part)
+            .map(|c| c.start_line)
+            .ok_or_else(|| match (chapters.first(), chapters.last()) {
+                (Some(first), Some(last)) => format!(
+                    "No chapter {} (chapters run from {} to {})",
+                    n, first.number, last.number
+                ),
+                _ => "The book has no chapters".to_string(),
+            }),
+        Target::Title(title) => {
+            let needle = title.to_lowercase();
+            // an exact title wins over one merely containing it
+            chapters
+                .iter()
+                .find(|c| c.title.to_lowercase() == needle)
+                .or_else(|| {
+                    chapters
+                        .iter()
+                        .find(|c| c.title.to_lowercase().contains(&needle))
+                })
+                .map(|c| c.start_line)
+                .ok_or_else(|| format!("No chapter title contains \"{}\"", title))
+        }
+    }
+}
+
+/// Chapter titles containing `input`, for tab completion.
+pub fn completions(input: &str, chapters: &[Chapter]) -> Vec<String> {
+    let needle = input.trim().to_lowercase();
+    chapters
+        .iter()
+        .filter(|c| c.title.to_lowercase().contains(&needle))
+        .map(|c| c.title.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_each_kind_of_target() {
+        assert_eq!(parse(" 123 "), Ok(Some(Target::Line(123))));
+        assert_eq!(parse("45%"), Ok(Some(Target::Percent(45.0))));
+        assert_eq!(parse("ch 300"), Ok(Some(Target::Chapter(300))));
+        assert_eq!(parse("Chapter 7"), Ok(Some(Target::Chapter(7))));
+        assert_eq!(
+            parse("dragon"),
+            Ok(Some(Target::Title("dragon".to_string())))
+        );
+        assert_eq!(parse("  "), Ok(None));
+    }
+
+    #[test]
+    fn line_zero_is_rejected() {
+        assert_eq!(parse("0"), Err(NO_LINE_ZERO.to_string()));
+        assert_eq!(parse(" 0 "), Err(NO_LINE_ZERO.to_string()));
+        // not a line number, so not line 0
+        assert_eq!(parse("0%"), Ok(Some(Target::Percent(0.0))));
+    }
+
+    #[test]
+    fn resolves_lines_from_one() {
+        assert_eq!(resolve(&Target::Line(1), &[], 10), Ok(0));
+        assert_eq!(resolve(&Target::Line(10), &[], 10), Ok(9));
+        assert!(resolve(&Target::Line(11), &[], 10).is_err());
+    }
+}
@@ -0,0 +1,179 @@
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use bincode::Options;
+use color_eyre::Result;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::analysis::Issue;
+use crate::chapter::{Chapter, HeadingOverrides};
+use crate::charset::Charset;
+use crate::loader::{Book, Layout};
+use crate::loading::Loaded;
+use crate::state;
+use crate::stats::LineCounts;
+
+/// Bumped whenever the index format or the way books are parsed changes, so
+/// indexes written before are not used.
+const VERSION: u32 = 3;
+
+/// What a book's index was made from. The index is only used when all of it
+/// matches; anything else about the file changing makes it stale.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Key {
+    size: u64,
+    modified: Option<SystemTime>,
+    hash: u64,
+    overrides: HeadingOverrides,
+    hide_duplicates: bool,
+    // asked for by the reader, rather than detected
+    encoding: Option<String>,
+}
+
+impl Key {
+    /// The key for the book at `path`, whose file holds `bytes`, read and
+    /// parsed with these settings.
+    pub fn new(
+        path: &Path,
+        bytes: &[u8],
+        encoding: Option<Charset>,
+        overrides: &HeadingOverrides,
+        hide_duplicates: bool,
+    ) -> Self {
+        Self {
+            size: bytes.len() as u64,
+            modified: fs::metadata(path).and_then(|m| m.modified()).ok(),
+            hash: content_hash(bytes),
+            overrides: overrides.clone(),
+            hide_duplicates,
+            encoding: encoding.map(|e| e.name().to_string()),
+        }
+    }
+}
+
+// what follows the key in an index file: the encoding's name, each line as
+// its gap from the end of the line before and its length, then `Loaded`
+type Stored = (
+    String,
+    Vec<(usize, usize)>,
+    Vec<Chapter>,
+    Vec<Vec<Issue>>,
+    Vec<usize>,
+    LineCounts,
+);
+
+/// The saved layout and parse of the book at `book`, if its index was made
+/// from the file as it is now.
+pub fn load(book: &Path, key: &Key) -> Option<(Layout, Loaded)> {
+    let file = File::open(path(book)?).ok()?;
+    // nothing in the file can be longer than it, however corrupt it is
+    let options = options().with_limit(file.metadata().ok()?.len());
+    let mut file = BufReader::new(file);
+    // the version comes first and alone, so any older layout is told apart
+    let version: u32 = options.deserialize_from(&mut file).ok()?;
+    if version != VERSION {
+        return None;
+    }
+    let saved: Key = options.deserialize_from(&mut file).ok()?;
+    if saved != *key {
+        return None;
+    }
+    let (encoding, gaps, chapters, issues, marked, line_counts): Stored =
+        options.deserialize_from(&mut file).ok()?;
+    let mut end = 0usize;
+    let lines = gaps
+        .into_iter()
+        .map(|(gap, len)| {
+            let start = end.saturating_add(gap);
+            end = start.saturating_add(len);
+            start..end
+        })
+        .collect();
+    let layout = Layout {
+        encoding: Charset::for_label(&encoding)?,
+        lines,
+    };
+    let loaded = Loaded {
+        chapters,
+        issues,
+        marked,
+        line_counts,
+    };
+    Some((layout, loaded))
+}
+
+/// Save the layout of `lines` and its parse under `key`, replacing the
+/// book's index.
+pub fn save(book: &Path, key: &Key, lines: &Book, loaded: &Loaded) -> Result<()> {
+    let Some(path) = path(book) else {
+        return Ok(());
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // written aside and renamed, so a reader never sees half an index
+    let tmp = path.with_extension("tmp");
+    let mut out = BufWriter::new(File::create(&tmp)?);
+    options().serialize_into(&mut out, &VERSION)?;
+    options().serialize_into(&mut out, key)?;
+    let stored = (
+        lines.encoding().name(),
+        Gaps(lines.line_ranges()),
+        &loaded.chapters,
+        &loaded.issues,
+        &loaded.marked,
+        &loaded.line_counts,
+    );
+    options().serialize_into(&mut out, &stored)?;
+    out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    fs::rename(&tmp, &path)?;
+    Ok(())
+}
+
+fn path(book: &Path) -> Option<PathBuf> {
+    state::data_dir().map(|d| {
+        d.join("index")
+            .join(format!("{}.bin", state::book_key(book)))
+    })
+}
+
+fn options() -> impl Options + Copy {
+    // variable-length integers keep line offsets to a byte or two each
+    bincode::options().allow_trailing_bytes()
+}
+
+// line ranges written as they are read back in `Stored`, without building
+// the list of gaps first
+struct Gaps<'a>(&'a [Range<usize>]);
+
+impl Serialize for Gaps<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut end = 0;
+        serializer.collect_seq(self.0.iter().map(|r| {
+            let gap = r.start - end;
+            end = r.end;
+            (gap, r.len())
+        }))
+    }
+}
+
+/// A quick 64-bit hash of a whole file, a word at a time. It only has to
+/// notice that a file changed, not stand up to anyone making it collide.
+fn content_hash(bytes: &[u8]) -> u64 {
+    const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+    let mix = |hash: u64, word: u64| (hash.rotate_left(5) ^ word).wrapping_mul(SEED);
+    let mut words = bytes.chunks_exact(8);
+    let mut hash = (&mut words).fold(0, |hash, w| {
+        mix(
+            hash,
+            u64::from_le_bytes(w.try_into().expect("8-byte chunk")),
+        )
+    });
+    for &b in words.remainder() {
+        hash = mix(hash, b as u64);
+    }
+    mix(hash, bytes.len() as u64)
+}
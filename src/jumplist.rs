@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+
+/// How many positions each direction keeps.
+const MAX_JUMPS: usize = 100;
+
+/// Back/forward history of the lines jumps were made from, counted in the
+/// whole file so they still hold when the book is split into chapters anew.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JumpList {
+    back_lines: Vec<usize>,
+    forward_lines: Vec<usize>,
+    // where the last recorded jump landed
+    #[serde(skip)]
+    landed: Option<usize>,
+}
+
+impl JumpList {
+    /// Note a jump from line `from` to line `to`. Jumps made one after another
+    /// without moving in between count as one.
+    pub fn record(&mut self, from: usize, to: usize) {
+        if from == to {
+            return;
+        }
+        if self.landed != Some(from) && self.back_lines.last() != Some(&from) {
+            self.back_lines.push(from);
+            if self.back_lines.len() > MAX_JUMPS {
+                self.back_lines.remove(0);
+            }
+        }
+        self.forward_lines.clear();
+        self.landed = Some(to);
+    }
+
+    /// Line to go back to from `current`, if any.
+    pub fn back(&mut self, current: usize) -> Option<usize> {
+        let target = self.back_lines.pop()?;
+        self.forward_lines.push(current);
+        self.landed = None;
+        Some(target)
+    }
+
+    /// Line to go forward to from `current`, if any.
+    pub fn forward(&mut self, current: usize) -> Option<usize> {
+        let target = self.forward_lines.pop()?;
+        self.back_lines.push(current);
+        self.landed = None;
+        Some(target)
+    }
+}
@@ -1,4 +1,25 @@
+pub mod analysis;
+pub mod annotation;
 pub mod app;
 pub mod args;
+pub mod autoscroll;
 pub mod bookmark;
 pub mod chapter;
+pub mod charset;
+pub mod commands;
+pub mod config;
+pub mod convert;
+pub mod dict;
+pub mod event;
+pub mod goto;
+pub mod index;
+pub mod jumplist;
+pub mod loader;
+pub mod loading;
+pub mod notes;
+pub mod number;
+pub mod state;
+pub mod stats;
+pub mod text;
+pub mod theme;
+pub mod tts;
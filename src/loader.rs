@@ -0,0 +1,445 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::ops::Range;
+use std::path::Path;
+
+use chardetng::EncodingDetector;
+use color_eyre::Result;
+use color_eyre::eyre::WrapErr;
+use encoding_rs::{
+    BIG5, EUC_JP, EUC_KR, Encoding, GB18030, GBK, SHIFT_JIS, UTF_8, UTF_16BE, UTF_16LE,
+    WINDOWS_1252,
+};
+use memmap2::Mmap;
+
+use crate::charset::Charset;
+
+/// How much of a non-UTF-8 file the encoding is guessed from, and how much
+/// of a file is decoded to compare encodings.
+const DETECT_BYTES: usize = 1 << 20;
+/// Longest line shown from a file decoded with each encoding.
+const PREVIEW_CHARS: usize = 60;
+/// Bytes decoded between progress reports.
+const DECODE_CHUNK: usize = 1 << 20;
+
+/// A book's text, held once: mapped from disk when it is UTF-8, decoded into
+/// memory otherwise. Lines are byte ranges into it, and lines changed since
+/// loading (bookmarks) are kept aside.
+#[derive(Default)]
+pub struct Book {
+    buffer: Buffer,
+    // what the file was decoded from
+    encoding: Charset,
+    // whether the file started with a byte order mark, left out of the text
+    bom: bool,
+    // characters the encoding could not decode, now U+FFFD
+    replaced: usize,
+    // byte range of each line, line ending left out
+    lines: Vec<Range<usize>>,
+    edits: HashMap<usize, String>,
+}
+
+/// What an earlier load found out about a file, so it need not be worked
+/// out again.
+pub struct Layout {
+    pub encoding: Charset,
+    /// byte range of each line in the decoded text
+    pub lines: Vec<Range<usize>>,
+}
+
+enum Buffer {
+    Mapped(Mmap),
+    Decoded(String),
+}
+
+impl Default for Buffer {
+    fn default() -> Self {
+        Buffer::Decoded(String::new())
+    }
+}
+
+impl fmt::Debug for Book {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Book")
+            .field("bytes", &self.text().len())
+            .field("encoding", &self.encoding.name())
+            .field("bom", &self.bom)
+            .field("replaced", &self.replaced)
+            .field("lines", &self.lines.len())
+            .field("edits", &self.edits.len())
+            .finish()
+    }
+}
+
+impl Book {
+    /// Read a book in `encoding`, or else in the charset its byte order mark
+    /// names, as UTF-8 if it is valid UTF-8, and in a detected encoding if
+    /// none of these.
+    pub fn open(path: &Path, encoding: Option<Charset>) -> Result<Self> {
+        Self::open_with(path, encoding, |_| None, |_, _| {})
+    }
+
+    /// Like [`Book::open`], first asking `known` whether the file's bytes
+    /// were laid out before, and calling `progress` with the bytes decoded so
+    /// far and the file size as it goes. A known layout skips detecting the
+    /// encoding and splitting lines.
+    pub fn open_with(
+        path: &Path,
+        encoding: Option<Charset>,
+        known: impl FnOnce(&[u8]) -> Option<Layout>,
+        mut progress: impl FnMut(u64, u64),
+    ) -> Result<Self> {
+        let file = File::open(path).wrap_err_with(|| format!("Cannot read {}", path.display()))?;
+        let len = file.metadata()?.len();
+        // an empty file cannot be mapped
+        if len == 0 {
+            return Ok(Self::default());
+        }
+        // SAFETY: the mapping is only read. noveltui replaces the file by
+        // renaming a new one over it (see `save`), which leaves the mapped
+        // inode as it was; another program truncating the file in place
+        // could still crash the reader, as with any mapped file.
+        let map = unsafe { Mmap::map(&file) }
+            .wrap_err_with(|| format!("Cannot read {}", path.display()))?;
+        let layout = known(&map);
+        let (encoding, bom, how) = sniff(&map, encoding.or(layout.as_ref().map(|l| l.encoding)));
+        let utf8 = encoding == Charset::default()
+            && (how == Detection::Utf8 || std::str::from_utf8(&map).is_ok());
+        let (buffer, replaced) = if utf8 {
+            // a byte order mark stays in the mapping; `split_lines` steps over it
+            (Buffer::Mapped(map), 0)
+        } else {
+            // including UTF-8 asked for by hand, decoded as well as it can be
+            let (text, replaced) = decode(&map[bom..], encoding, &mut progress);
+            (Buffer::Decoded(text), replaced)
+        };
+        progress(len, len);
+        let mut book = Self {
+            buffer,
+            encoding,
+            bom: bom > 0,
+            replaced,
+            ..Self::default()
+        };
+        match layout {
+            Some(layout) if layout.encoding == encoding && book.fits(&layout.lines) => {
+                book.lines = layout.lines;
+            }
+            _ => book.split_lines(),
+        }
+        Ok(book)
+    }
+
+    /// A book from text already in memory.
+    pub fn from_string(text: String) -> Self {
+        let mut book = Self {
+            buffer: Buffer::Decoded(text),
+            ..Self::default()
+        };
+        book.split_lines();
+        book
+    }
+
+    // whether `lines` can be lines of this text, as a layout from an
+    // earlier load should be
+    fn fits(&self, lines: &[Range<usize>]) -> bool {
+        let text = self.text();
+        let mut end = 0;
+        lines.iter().all(|r| {
+            let ok = end <= r.start
+                && r.start <= r.end
+                && r.end <= text.len()
+                && text.is_char_boundary(r.start)
+                && text.is_char_boundary(r.end);
+            end = r.end;
+            ok
+        })
+    }
+
+    fn split_lines(&mut self) {
+        let text = self.text();
+        let base = text.as_ptr() as usize;
+        // a byte order mark is no part of the first line
+        let lines = text
+            .strip_prefix('\u{FEFF}')
+            .unwrap_or(text)
+            .lines()
+            .map(|l| {
+                let start = l.as_ptr() as usize - base;
+                start..start + l.len()
+            })
+            .collect();
+        self.lines = lines;
+    }
+
+    fn text(&self) -> &str {
+        match &self.buffer {
+            // SAFETY: checked to be UTF-8 in `open`, and never written to
+            Buffer::Mapped(map) => unsafe { std::str::from_utf8_unchecked(map) },
+            Buffer::Decoded(s) => s,
+        }
+    }
+
+    /// The encoding the file was decoded from.
+    pub fn encoding(&self) -> Charset {
+        self.encoding
+    }
+
+    /// Characters that could not be decoded and show as U+FFFD, a sign of a
+    /// wrong encoding.
+    pub fn replaced(&self) -> usize {
+        self.replaced
+    }
+
+    /// Byte range of each line in the text as loaded, edits aside, to be
+    /// handed back through a [`Layout`] next time.
+    pub fn line_ranges(&self) -> &[Range<usize>] {
+        &self.lines
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Line `i`, or `None` past the end.
+    pub fn get(&self, i: usize) -> Option<&str> {
+        if let Some(edited) = self.edits.get(&i) {
+            return Some(edited);
+        }
+        self.lines.get(i).map(|r| &self.text()[r.clone()])
+    }
+
+    /// Line `i`; empty past the end.
+    pub fn line(&self, i: usize) -> &str {
+        self.get(i).unwrap_or("")
+    }
+
+    /// The lines in `range`, clipped to the book.
+    pub fn range(&self, range: Range<usize>) -> impl Iterator<Item = &str> {
+        let end = range.end.min(self.len());
+        (range.start.min(end)..end).map(|i| self.line(i))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.range(0..self.len())
+    }
+
+    /// Replace line `i`; ignored past the end.
+    pub fn set_line(&mut self, i: usize, line: String) {
+        if i < self.len() {
+            self.edits.insert(i, line);
+        }
+    }
+
+    /// Write the book to `path` as UTF-8, or as it was read if it had a byte
+    /// order mark, mark included. The file is written aside and renamed over
+    /// the old one, so a mapped copy of that stays readable. From then on the
+    /// book's encoding is the one it was written in.
+    pub fn save(&mut self, path: &Path) -> Result<()> {
+        let charset = if self.bom {
+            self.encoding
+        } else {
+            Charset::default()
+        };
+        replace_file(path, false, |out| {
+            out.write_all(if self.bom { charset.bom() } else { b"" })?;
+            for (i, line) in self.iter().enumerate() {
+                if i > 0 {
+                    charset.write(out, "\n")?;
+                }
+                charset.write(out, line)?;
+            }
+            Ok(())
+        })?;
+        self.encoding = charset;
+        Ok(())
+    }
+}
+
+/// Replace the file at `path` with what `write` writes. It is written to a
+/// file alongside and renamed over the old one, so the book is never left
+/// half written; a link keeps pointing at it, and it keeps its permissions
+/// and, if `keep_mtime`, its modification time.
+pub fn replace_file(
+    path: &Path,
+    keep_mtime: bool,
+    write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+) -> Result<()> {
+    // through a link, the book is the file it points to
+    let path = fs::canonicalize(path)?;
+    let metadata = fs::metadata(&path)?;
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = path.with_file_name(format!(".{}.tmp", name));
+    let mut out = BufWriter::new(File::create(&tmp)?);
+    let written = (|| {
+        write(&mut out)?;
+        let file = out.into_inner().map_err(|e| e.into_error())?;
+        file.set_permissions(metadata.permissions())?;
+        if keep_mtime {
+            file.set_modified(metadata.modified()?)?;
+        }
+        file.sync_all()?;
+        fs::rename(&tmp, &path)
+    })();
+    if written.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    Ok(written?)
+}
+
+/// How the charset of a book was settled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Detection {
+    /// asked for
+    Given,
+    /// named by a byte order mark
+    Bom,
+    /// all of it is valid UTF-8
+    Utf8,
+    /// guessed from the start of the text; a guess that did not score above
+    /// any other encoding is likely wrong
+    Guessed { confident: bool },
+}
+
+/// The charset a book holding `bytes` is read in: `encoding` if given, or
+/// else the one its byte order mark names, UTF-8 if it is valid UTF-8, and a
+/// guess if none of these. Also returns the length of the byte order mark to
+/// skip, if the charset is the one it names.
+pub fn sniff(bytes: &[u8], encoding: Option<Charset>) -> (Charset, usize, Detection) {
+    let bom = Charset::from_bom(bytes);
+    match (encoding, bom) {
+        (Some(given), Some((bom, len))) if given == bom => (given, len, Detection::Given),
+        (Some(given), _) => (given, 0, Detection::Given),
+        (None, Some((bom, len))) => (bom, len, Detection::Bom),
+        (None, None) if std::str::from_utf8(bytes).is_ok() => {
+            (Charset::default(), 0, Detection::Utf8)
+        }
+        (None, None) => {
+            let (guess, confident) = detect(bytes);
+            (
+                Charset::Encoding(guess),
+                0,
+                Detection::Guessed { confident },
+            )
+        }
+    }
+}
+
+fn detect(bytes: &[u8]) -> (&'static Encoding, bool) {
+    let mut det = EncodingDetector::new();
+    // the start of a book tells the encoding as well as all of it
+    let sample = &bytes[..bytes.len().min(DETECT_BYTES)];
+    det.feed(sample, sample.len() == bytes.len());
+    det.guess_assess(None, true)
+}
+
+/// The text of `bytes` in `charset`, and how many characters it could not
+/// decode. `progress` is called with the bytes decoded so far as it goes.
+pub fn decode(
+    bytes: &[u8],
+    charset: Charset,
+    progress: &mut impl FnMut(u64, u64),
+) -> (String, usize) {
+    // decoded a chunk at a time, to report progress on big files
+    let mut decoder = charset.decoder();
+    let mut out = String::new();
+    let mut replaced = 0;
+    let mut done = 0;
+    while done < bytes.len() {
+        let end = (done + DECODE_CHUNK).min(bytes.len());
+        let chunk = &bytes[done..end];
+        out.reserve(decoder.max_utf8_buffer_length(chunk.len()));
+        let from = out.len();
+        let (read, had_errors) = decoder.decode_to_string(chunk, &mut out, end == bytes.len());
+        if had_errors {
+            replaced += out[from..].matches(char::REPLACEMENT_CHARACTER).count();
+        }
+        done += read;
+        progress(done as u64, bytes.len() as u64);
+    }
+    out.shrink_to_fit();
+    (out, replaced)
+}
+
+/// How the start of a book reads in some charset, for picking one by hand.
+#[derive(Debug)]
+pub struct Trial {
+    pub encoding: Charset,
+    /// whether this is the charset the book would be read in unasked
+    pub detected: bool,
+    /// characters of the start that could not be decoded
+    pub errors: usize,
+    /// the first line with any text on it
+    pub preview: String,
+}
+
+/// The charsets the book at `path` might be in, tried on its start: the
+/// detected one first, then the rest with the fewest errors first.
+pub fn try_encodings(path: &Path) -> Result<Vec<Trial>> {
+    let mut sample = Vec::new();
+    File::open(path)
+        .and_then(|f| f.take(DETECT_BYTES as u64).read_to_end(&mut sample))
+        .wrap_err_with(|| format!("Cannot read {}", path.display()))?;
+    let bom = Charset::from_bom(&sample);
+    let detected = match bom {
+        Some((bom, _)) => bom,
+        // a character cut off at the end of the sample does not count against UTF-8
+        None => match std::str::from_utf8(&sample) {
+            Err(e) if e.error_len().is_some() => Charset::Encoding(detect(&sample).0),
+            _ => Charset::default(),
+        },
+    };
+    let mut encodings: Vec<Charset> = [
+        UTF_8,
+        GB18030,
+        GBK,
+        BIG5,
+        SHIFT_JIS,
+        EUC_JP,
+        EUC_KR,
+        WINDOWS_1252,
+        UTF_16LE,
+        UTF_16BE,
+    ]
+    .into_iter()
+    .map(Charset::Encoding)
+    .collect();
+    if !encodings.contains(&detected) {
+        encodings.push(detected);
+    }
+    let mut trials: Vec<Trial> = encodings
+        .into_iter()
+        .map(|encoding| {
+            let text = match bom {
+                Some((bom, len)) if bom == encoding => &sample[len..],
+                _ => &sample[..],
+            };
+            let mut decoder = encoding.decoder();
+            let mut out = String::with_capacity(decoder.max_utf8_buffer_length(text.len()));
+            decoder.decode_to_string(text, &mut out, false);
+            Trial {
+                encoding,
+                detected: encoding == detected,
+                errors: out.matches(char::REPLACEMENT_CHARACTER).count(),
+                preview: out
+                    .lines()
+                    .map(str::trim)
+                    .find(|l| !l.is_empty())
+                    .unwrap_or("")
+                    .chars()
+                    .take(PREVIEW_CHARS)
+                    // a wrong guess can turn up control characters
+                    .map(|c| if c.is_control() { ' ' } else { c })
+                    .collect(),
+            }
+        })
+        .collect();
+    trials.sort_by_key(|t| (!t.detected, t.errors));
+    Ok(trials)
+}
@@ -0,0 +1,138 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use color_eyre::Result;
+
+use crate::analysis::{self, Issue};
+use crate::bookmark;
+use crate::chapter::{self, Chapter, HeadingOverrides};
+use crate::charset::Charset;
+use crate::index::{self, Key};
+use crate::loader::Book;
+use crate::stats::LineCounts;
+
+/// How often the worker reports progress and sends the chapters found.
+const REPORT_EVERY: Duration = Duration::from_millis(100);
+
+/// What the loading worker sends back, in this order.
+pub enum Update {
+    /// bytes of the file read and decoded so far, out of `total`
+    Decoded { done: u64, total: u64 },
+    /// the text, while its chapters are still being found
+    Text(Arc<Book>),
+    /// chapters found since the last update, in book order
+    Chapters(Vec<Chapter>),
+    /// the finished result; its chapters replace those sent before
+    Done(Result<Loaded>),
+}
+
+/// A fully parsed and checked book.
+pub struct Loaded {
+    pub chapters: Vec<Chapter>,
+    pub issues: Vec<Vec<Issue>>,
+    /// lines ending in the bookmark symbol
+    pub marked: Vec<usize>,
+    pub line_counts: LineCounts,
+}
+
+/// Load `path` on a worker thread, in `encoding` if given; updates arrive on
+/// the returned channel.
+pub fn spawn(
+    path: PathBuf,
+    encoding: Option<Charset>,
+    overrides: HeadingOverrides,
+    hide_duplicates: bool,
+) -> Receiver<Update> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let result = load(&path, encoding, &overrides, hide_duplicates, &tx);
+        // the reader may have quit already
+        let _ = tx.send(Update::Done(result));
+    });
+    rx
+}
+
+fn load(
+    path: &Path,
+    encoding: Option<Charset>,
+    overrides: &HeadingOverrides,
+    hide_duplicates: bool,
+    tx: &Sender<Update>,
+) -> Result<Loaded> {
+    // a book read before comes with its index, unless the file has changed
+    let mut key = None;
+    let mut cached = None;
+    let mut last = Instant::now();
+    let book = Arc::new(Book::open_with(
+        path,
+        encoding,
+        |bytes| {
+            let key = key.insert(Key::new(path, bytes, encoding, overrides, hide_duplicates));
+            let (layout, loaded) = index::load(path, key)?;
+            cached = Some((layout.lines.len(), loaded));
+            Some(layout)
+        },
+        |done, total| {
+            if last.elapsed() >= REPORT_EVERY {
+                last = Instant::now();
+                let _ = tx.send(Update::Decoded { done, total });
+            }
+        },
+    )?);
+    let _ = tx.send(Update::Text(Arc::clone(&book)));
+    if let Some((lines, loaded)) = cached
+        && lines == book.len()
+    {
+        return Ok(loaded);
+    }
+
+    // the first chapter goes out at once so it can be shown, then batches
+    let mut batch = Vec::new();
+    let mut sent_any = false;
+    let mut last = Instant::now();
+    let (chapters, issues) = parse(&book, overrides, hide_duplicates, |ch| {
+        batch.push(ch);
+        if !sent_any || last.elapsed() >= REPORT_EVERY {
+            sent_any = true;
+            last = Instant::now();
+            let _ = tx.send(Update::Chapters(std::mem::take(&mut batch)));
+        }
+    });
+    let loaded = Loaded {
+        chapters,
+        issues,
+        marked: bookmark::marked_lines(&book),
+        line_counts: LineCounts::new(&book),
+    };
+    if let Some(key) = &key {
+        // without an index the book is only slower to open next time
+        let _ = index::save(path, key, &book, &loaded);
+    }
+    Ok(loaded)
+}
+
+/// Chapters of `lines` and the problems found in them, leaving out exact
+/// duplicates when `hide_duplicates` is set. `found` sees chapters as they
+/// are parsed, before any are left out.
+pub fn parse(
+    lines: &Book,
+    overrides: &HeadingOverrides,
+    hide_duplicates: bool,
+    found: impl FnMut(Chapter),
+) -> (Vec<Chapter>, Vec<Vec<Issue>>) {
+    let mut chapters = chapter::parse_lines_streaming(lines, overrides, found);
+    let mut issues = analysis::analyze(&chapters, lines);
+    if hide_duplicates {
+        let mut flagged = issues.iter();
+        chapters.retain(|_| {
+            !flagged
+                .next()
+                .is_some_and(|i| i.iter().any(|i| matches!(i, Issue::ExactDuplicate { .. })))
+        });
+        issues = analysis::analyze(&chapters, lines);
+    }
+    (chapters, issues)
+}
@@ -1,11 +1,46 @@
+use std::io::stdout;
+
 use clap::Parser;
 use color_eyre::Result;
-use noveltui::{app::App, args::Options};
+use crossterm::{
+    event::{
+        DisableBracketedPaste, DisableFocusChange, DisableMouseCapture, EnableBracketedPaste,
+        EnableFocusChange, EnableMouseCapture,
+    },
+    execute,
+};
+use noveltui::{app::App, args::Options, commands, config::Config};
 fn main() -> Result<()> {
-    let args = Options::parse();
+    let mut args = Options::parse();
+    if let Some(command) = args.command.take() {
+        return commands::run(command);
+    }
+    let config = Config::load(args.config.as_deref())?;
+    let mouse = !args.no_mouse && config.mouse.unwrap_or(true);
+    let mut app = App::new(args, config)?;
     let terminal = ratatui::init();
-    let result = App::new(args).run(terminal);
+    // ratatui's hook restores the screen; the modes turned on here go first
+    let restore = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = execute!(
+            stdout(),
+            DisableMouseCapture,
+            DisableBracketedPaste,
+            DisableFocusChange
+        );
+        restore(info);
+    }));
+    let _ = execute!(stdout(), EnableFocusChange, EnableBracketedPaste);
+    if mouse {
+        // not fatal: the reader still works from the keyboard
+        let _ = execute!(stdout(), EnableMouseCapture);
+    }
+    let result = app.run(terminal);
 
+    if mouse {
+        let _ = execute!(stdout(), DisableMouseCapture);
+    }
+    let _ = execute!(stdout(), DisableBracketedPaste, DisableFocusChange);
     ratatui::restore();
     result
 }
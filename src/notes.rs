@@ -0,0 +1,199 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use clap::ValueEnum;
+use color_eyre::Result;
+use color_eyre::eyre::WrapErr;
+use serde::Serialize;
+
+use crate::bookmark::{self, Bookmark};
+use crate::chapter::{self, Chapter};
+use crate::loader::Book;
+use crate::state::{self, BookState};
+
+/// Output format for exported notes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    #[value(alias = "markdown")]
+    Md,
+    Json,
+    Csv,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Md => "md",
+            Format::Json => "json",
+            Format::Csv => "csv",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NoteKind {
+    Bookmark,
+    Highlight,
+}
+
+/// A bookmark or annotation, located for export. A bookmark's note is its label.
+#[derive(Debug, Clone, Serialize)]
+pub struct Note {
+    pub kind: NoteKind,
+    /// title of the chapter holding the note, empty for books without chapters
+    pub chapter: String,
+    pub chapter_number: Option<usize>,
+    /// 1-based line within the chapter
+    pub line: usize,
+    /// 1-based line within the file
+    pub global_line: usize,
+    pub quote: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub note: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// seconds since the Unix epoch, unknown for bookmarks made outside noveltui
+    pub created: Option<u64>,
+}
+
+/// All bookmarks and annotations of a book, in reading order.
+pub fn collect(chapters: &[Chapter], bookmarks: &[Bookmark], book_state: &BookState) -> Vec<Note> {
+    let mut notes = Vec::new();
+    for b in bookmarks {
+        let Some(ch) = chapters.get(b.chapter_index) else {
+            continue;
+        };
+        notes.push(Note {
+            kind: NoteKind::Bookmark,
+            chapter: ch.title.clone(),
+            chapter_number: Some(ch.number),
+            line: b.line_in_chapter + 1,
+            global_line: b.line + 1,
+            quote: b.line_content.clone(),
+            note: b.label.clone(),
+            color: None,
+            created: b.created,
+        });
+    }
+    for a in &book_state.annotations {
+        let line = a.start.line;
+        let chapter = chapters.iter().rposition(|c| c.start_line <= line);
+        let ch = chapter.map(|i| &chapters[i]);
+        notes.push(Note {
+            kind: NoteKind::Highlight,
+            chapter: ch.map(|c| c.title.clone()).unwrap_or_default(),
+            chapter_number: ch.map(|c| c.number),
+            line: line - ch.map_or(0, |c| c.start_line) + 1,
+            global_line: line + 1,
+            quote: a.quote.clone(),
+            note: a.note.clone(),
+            color: Some(a.color.clone()),
+            created: Some(a.created),
+        });
+    }
+    notes.sort_by_key(|n| (n.global_line, n.kind == NoteKind::Highlight));
+    notes
+}
+
+/// Render `notes` for the book titled `title`.
+pub fn render(title: &str, notes: &[Note], format: Format) -> Result<String> {
+    Ok(match format {
+        Format::Md => markdown(title, notes),
+        Format::Json => serde_json::to_string_pretty(notes)? + "\n",
+        Format::Csv => csv(notes),
+    })
+}
+
+fn markdown(title: &str, notes: &[Note]) -> String {
+    let mut out = format!("# {}\n", title);
+    let mut chapter = None;
+    for n in notes {
+        if chapter != Some(&n.chapter) {
+            chapter = Some(&n.chapter);
+            let heading = if n.chapter.is_empty() {
+                "Notes"
+            } else {
+                &n.chapter
+            };
+            out.push_str(&format!("\n## {}\n", heading));
+        }
+        out.push_str(&format!("\n> {}\n", n.quote));
+        if !n.note.is_empty() {
+            out.push_str(&format!("\n{}\n", n.note));
+        }
+        let mut meta = vec![match n.kind {
+            NoteKind::Bookmark => "Bookmark".to_string(),
+            NoteKind::Highlight => format!("Highlight ({})", n.color.as_deref().unwrap_or("")),
+        }];
+        if let Some(num) = n.chapter_number {
+            meta.push(format!("chapter {}", num));
+        }
+        meta.push(format!("line {}", n.line));
+        if let Some(t) = n.created {
+            meta.push(format!("{} UTC", state::format_time(t)));
+        }
+        out.push_str(&format!("\n*{}*\n", meta.join(" · ")));
+    }
+    out
+}
+
+fn csv(notes: &[Note]) -> String {
+    let mut out =
+        String::from("kind,chapter,chapter_number,line,global_line,quote,note,color,created\n");
+    for n in notes {
+        let fields = [
+            match n.kind {
+                NoteKind::Bookmark => "bookmark".to_string(),
+                NoteKind::Highlight => "highlight".to_string(),
+            },
+            n.chapter.clone(),
+            n.chapter_number.map(|c| c.to_string()).unwrap_or_default(),
+            n.line.to_string(),
+            n.global_line.to_string(),
+            n.quote.clone(),
+            n.note.clone(),
+            n.color.clone().unwrap_or_default(),
+            n.created.map(state::format_time).unwrap_or_default(),
+        ];
+        let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Title used in exports: the book's file name without extension.
+pub fn book_title(book: &Path) -> String {
+    book.file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// `noveltui notes export`: write the notes of `book` to `output`, or stdout.
+pub fn export(book: &Path, format: Format, output: Option<&Path>) -> Result<()> {
+    let book_state = BookState::load(book);
+    let lines = Book::open(book, book_state.encoding())?;
+    let chapters = chapter::parse_lines(&lines, &book_state.headings);
+    let mut bookmarks = bookmark::parse_bookmarks(&chapters, &lines);
+    bookmark::apply_info(&mut bookmarks, &book_state.bookmarks);
+    let notes = collect(&chapters, &bookmarks, &book_state);
+    let out = render(&book_title(book), &notes, format)?;
+    match output {
+        Some(path) => {
+            fs::write(path, out).wrap_err_with(|| format!("Cannot write {}", path.display()))?;
+            eprintln!("Exported {} notes to {}", notes.len(), path.display());
+        }
+        None => std::io::stdout().write_all(out.as_bytes())?,
+    }
+    Ok(())
+}
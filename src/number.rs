@@ -0,0 +1,244 @@
+/// Roman numerals in headings above this are more likely words (`MIX`, `DIV`).
+const MAX_ROMAN_HEADING: usize = 999;
+
+/// The number `text` starts with, and the text after it. Understands digits,
+/// Roman numerals (`XII`), number words (`Twenty-One`, `twenty first`) and a
+/// leading `the` (`THE FIRST`).
+pub fn parse(text: &str) -> Option<(usize, &str)> {
+    let text = text.trim_start();
+    let digits = text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits > 0 {
+        return Some((text[..digits].parse().ok()?, &text[digits..]));
+    }
+    let (first, after) = split_word(text);
+    if let Some(n) = roman(first).filter(|&n| n <= MAX_ROMAN_HEADING) {
+        return Some((n, after));
+    }
+    let text = if first.eq_ignore_ascii_case("the") {
+        after.trim_start()
+    } else {
+        text
+    };
+    words(text)
+}
+
+// `Twenty-One`, `one hundred and five`, `the first`; stops at the first word
+// that is not part of the number
+fn words(text: &str) -> Option<(usize, &str)> {
+    let mut value = 0;
+    let mut found = false;
+    let mut rest = text;
+    loop {
+        let (word, after) = split_word(rest.trim_start_matches([' ', '-']));
+        let lower = word.to_ascii_lowercase();
+        if found && lower == "and" && value >= 100 {
+            let (next, _) = split_word(after.trim_start());
+            if word_value(&next.to_ascii_lowercase()).is_some() {
+                rest = after;
+                continue;
+            }
+        }
+        let Some((n, ordinal)) = word_value(&lower) else {
+            break;
+        };
+        value = if n == 100 {
+            value.max(1) * 100
+        } else {
+            value + n
+        };
+        found = true;
+        rest = after;
+        // `twenty first` ends at `first`
+        if ordinal {
+            break;
+        }
+    }
+    found.then_some((value, rest))
+}
+
+// the leading run of letters and what follows it
+fn split_word(text: &str) -> (&str, &str) {
+    let end = text
+        .find(|c: char| !c.is_alphabetic())
+        .unwrap_or(text.len());
+    text.split_at(end)
+}
+
+// value of a number word, and whether it is an ordinal
+fn word_value(word: &str) -> Option<(usize, bool)> {
+    const CARDINALS: [&str; 19] = [
+        "one",
+        "two",
+        "three",
+        "four",
+        "five",
+        "six",
+        "seven",
+        "eight",
+        "nine",
+        "ten",
+        "eleven",
+        "twelve",
+        "thirteen",
+        "fourteen",
+        "fifteen",
+        "sixteen",
+        "seventeen",
+        "eighteen",
+        "nineteen",
+    ];
+    const ORDINALS: [&str; 19] = [
+        "first",
+        "second",
+        "third",
+        "fourth",
+        "fifth",
+        "sixth",
+        "seventh",
+        "eighth",
+        "ninth",
+        "tenth",
+        "eleventh",
+        "twelfth",
+        "thirteenth",
+        "fourteenth",
+        "fifteenth",
+        "sixteenth",
+        "seventeenth",
+        "eighteenth",
+        "nineteenth",
+    ];
+    const TENS: [&str; 8] = [
+        "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+    ];
+    const TENTHS: [&str; 8] = [
+        "twentieth",
+        "thirtieth",
+        "fortieth",
+        "fiftieth",
+        "sixtieth",
+        "seventieth",
+        "eightieth",
+        "ninetieth",
+    ];
+    let position = |list: &[&str]| list.iter().position(|w| *w == word);
+    if let Some(i) = position(&CARDINALS) {
+        Some((i + 1, false))
+    } else if let Some(i) = position(&ORDINALS) {
+        Some((i + 1, true))
+    } else if let Some(i) = position(&TENS) {
+        Some((i * 10 + 20, false))
+    } else if let Some(i) = position(&TENTHS) {
+        Some((i * 10 + 20, true))
+    } else {
+        match word {
+            "hundred" => Some((100, false)),
+            "hundredth" => Some((100, true)),
+            _ => None,
+        }
+    }
+}
+
+/// Value of a Roman numeral such as `XII` or `xii`, if `s` is one.
+pub fn roman(s: &str) -> Option<usize> {
+    if s.is_empty() {
+        return None;
+    }
+    let digit = |c: char| match c.to_ascii_uppercase() {
+        'I' => Some(1),
+        'V' => Some(5),
+        'X' => Some(10),
+        'L' => Some(50),
+        'C' => Some(100),
+        'D' => Some(500),
+        'M' => Some(1000),
+        _ => None,
+    };
+    let digits: Vec<usize> = s.chars().map(digit).collect::<Option<_>>()?;
+    let mut value = 0;
+    for (i, &d) in digits.iter().enumerate() {
+        match digits.get(i + 1) {
+            Some(&next) if next > d => value -= d as isize,
+            _ => value += d as isize,
+        }
+    }
+    let value = usize::try_from(value).ok().filter(|&v| v > 0)?;
+    // only the canonical spelling, so words like `DID` or `LID` are not numbers
+    (to_roman(value).eq_ignore_ascii_case(s)).then_some(value)
+}
+
+fn to_roman(mut n: usize) -> String {
+    const PARTS: [(usize, &str); 13] = [
+        (1000, "M"),
+        (900, "CM"),
+        (500, "D"),
+        (400, "CD"),
+        (100, "C"),
+        (90, "XC"),
+        (50, "L"),
+        (40, "XL"),
+        (10, "X"),
+        (9, "IX"),
+        (5, "V"),
+        (4, "IV"),
+        (1, "I"),
+    ];
+    let mut out = String::new();
+    for (value, numeral) in PARTS {
+        while n >= value {
+            out.push_str(numeral);
+            n -= value;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonical_roman_numerals_are_numbers() {
+        assert_eq!(roman("XII"), Some(12));
+        assert_eq!(roman("xiv"), Some(14));
+        assert_eq!(roman("MCMXCIV"), Some(1994));
+    }
+
+    #[test]
+    fn non_canonical_roman_numerals_are_not() {
+        // `IIII` and `VX` spell 4 and 5 the long way; `DID` is a word
+        for s in ["IIII", "VX", "IC", "DID", "LID", ""] {
+            assert_eq!(roman(s), None, "{s}");
+        }
+    }
+
+    #[test]
+    fn roman_spelling_round_trips() {
+        for n in 1..=3999 {
+            assert_eq!(roman(&to_roman(n)), Some(n));
+        }
+    }
+
+    #[test]
+    fn parses_digits_and_words() {
+        assert_eq!(parse("12: The Road"), Some((12, ": The Road")));
+        assert_eq!(parse("Twenty-One: The Road"), Some((21, ": The Road")));
+        assert_eq!(parse("twenty first"), Some((21, "")));
+        assert_eq!(parse("one hundred and five"), Some((105, "")));
+        assert_eq!(parse("Nothing"), None);
+    }
+
+    #[test]
+    fn parses_a_leading_the() {
+        assert_eq!(parse("the first"), Some((1, "")));
+        assert_eq!(parse("THE FIRST DAY"), Some((1, " DAY")));
+    }
+
+    #[test]
+    fn roman_headings_stop_at_999() {
+        assert_eq!(parse("CMXCIX. The End"), Some((999, ". The End")));
+        // `MIX` is 1009, more likely the word
+        assert_eq!(parse("M"), None);
+        assert_eq!(parse("MIX"), None);
+    }
+}
@@ -0,0 +1,203 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::annotation::Annotation;
+use crate::bookmark::BookmarkSort;
+use crate::chapter::HeadingOverrides;
+use crate::charset::Charset;
+use crate::config::LayoutConfig;
+use crate::jumplist::JumpList;
+use crate::stats::Session;
+
+/// Directory for data noveltui writes itself, e.g. `~/.local/share/noveltui`.
+pub fn data_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|d| d.join("noveltui"))
+}
+
+/// UI choices remembered between sessions.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiState {
+    /// last pane layout; takes precedence over `[layout]` in the config
+    pub layout: Option<LayoutConfig>,
+    /// order of the bookmark menu
+    pub bookmark_sort: BookmarkSort,
+    /// `:` commands, oldest first
+    pub goto_history: Vec<String>,
+}
+
+impl UiState {
+    fn path() -> Option<PathBuf> {
+        data_dir().map(|d| d.join("state.toml"))
+    }
+
+    /// Load the saved state. A missing or unreadable file gives the default state.
+    pub fn load() -> Self {
+        Self::path()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|s| toml::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+/// Where the reader was in a book.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadingPosition {
+    pub chapter: usize,
+    pub line: usize,
+}
+
+/// Per-book data kept outside the book file itself.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BookState {
+    /// the book file, so data can be listed without it
+    pub path: Option<PathBuf>,
+    /// position when the book was last left
+    pub position: Option<ReadingPosition>,
+    /// words looked up while reading this book
+    pub vocabulary: Vec<VocabEntry>,
+    /// highlighted ranges and notes, ordered by start
+    pub annotations: Vec<Annotation>,
+    /// details about bookmarks, which themselves live in the book file
+    pub bookmarks: Vec<BookmarkInfo>,
+    /// positions left by jumps, for going back and forth
+    pub jumps: JumpList,
+    /// reading sessions, oldest first
+    pub sessions: Vec<Session>,
+    /// chapter headings demoted or promoted by hand
+    pub headings: HeadingOverrides,
+    /// encoding picked by hand in place of the detected one
+    pub encoding: Option<String>,
+}
+
+/// Label and creation time of a bookmark; matched to it by global line.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BookmarkInfo {
+    pub line: usize,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub label: String,
+    /// seconds since the Unix epoch
+    pub created: Option<u64>,
+}
+
+/// A looked-up word and where it was met.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VocabEntry {
+    pub word: String,
+    /// first definition found, empty when no dictionary had the word
+    pub definition: String,
+    /// the line the word was picked from
+    pub context: String,
+    pub chapter: usize,
+    pub line: usize,
+    /// seconds since the Unix epoch
+    pub added: u64,
+}
+
+impl BookState {
+    fn path(book: &Path) -> Option<PathBuf> {
+        data_dir().map(|d| d.join("books").join(format!("{}.toml", book_key(book))))
+    }
+
+    /// The encoding picked for the book, if any and still known.
+    pub fn encoding(&self) -> Option<Charset> {
+        self.encoding.as_deref().and_then(Charset::for_label)
+    }
+
+    /// Load the state for `book`. A missing or unreadable file gives the default state.
+    pub fn load(book: &Path) -> Self {
+        Self::path(book)
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|s| toml::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, book: &Path) -> Result<()> {
+        let Some(path) = Self::path(book) else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+/// Every book with saved state.
+pub fn all_books() -> Vec<BookState> {
+    let Some(dir) = data_dir().map(|d| d.join("books")) else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|e| e.path().extension().is_some_and(|x| x == "toml"))
+        .filter_map(|e| fs::read_to_string(e.path()).ok())
+        .filter_map(|s| toml::from_str(&s).ok())
+        .collect()
+}
+
+/// Seconds since the Unix epoch.
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Format seconds since the Unix epoch as `YYYY-MM-DD HH:MM` UTC.
+pub fn format_time(secs: u64) -> String {
+    let (y, m, d) = civil_from_days((secs / 86400) as i64);
+    let rem = secs % 86400;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        y,
+        m,
+        d,
+        rem / 3600,
+        rem % 3600 / 60
+    )
+}
+
+/// Calendar date of a day count since 1970-01-01 (Howard Hinnant's algorithm).
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    (y, m, d)
+}
+
+/// Stable file name for a book: FNV-1a of its absolute path.
+pub fn book_key(book: &Path) -> String {
+    let abs = fs::canonicalize(book).unwrap_or_else(|_| book.to_path_buf());
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in abs.to_string_lossy().as_bytes() {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
@@ -0,0 +1,316 @@
+use std::ops::Range;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::loader::Book;
+use crate::notes;
+use crate::state::{self, BookState};
+use crate::text::{self, Counts};
+
+/// Reading time stops counting after this long without input or auto-advance.
+pub const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+/// Sessions shorter than this are not kept.
+const MIN_SESSION_SECS: u64 = 10;
+/// Reading speed is only estimated from at least this much reading.
+const MIN_SPEED_SECS: u64 = 60;
+
+const DAY: u64 = 86400;
+/// Lines between the running totals kept by [`LineCounts`].
+const COUNT_STRIDE: usize = 64;
+
+/// One sitting with a book.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    /// seconds since the Unix epoch
+    pub start: u64,
+    /// time spent reading, idle time left out
+    pub seconds: u64,
+    /// lines advanced past
+    pub lines: u64,
+    #[serde(flatten)]
+    pub read: Counts,
+    pub chapters_finished: u32,
+}
+
+/// Follows the reader through a session.
+#[derive(Debug)]
+pub struct Tracker {
+    pub session: Session,
+    elapsed: Duration,
+    last_tick: Instant,
+    last_activity: Instant,
+    focused: bool,
+    // furthest global line and chapter reached since the last jump
+    furthest: usize,
+    chapter: usize,
+}
+
+impl Tracker {
+    pub fn new(line: usize, chapter: usize) -> Self {
+        let now = Instant::now();
+        Self {
+            session: Session {
+                start: state::now_secs(),
+                ..Session::default()
+            },
+            elapsed: Duration::ZERO,
+            last_tick: now,
+            last_activity: now,
+            focused: true,
+            furthest: line,
+            chapter,
+        }
+    }
+
+    /// The reader did something, so they are not idle.
+    pub fn activity(&mut self) {
+        self.last_activity = Instant::now();
+    }
+
+    pub fn set_focused(&mut self, focused: bool) {
+        self.tick();
+        self.focused = focused;
+        if focused {
+            self.activity();
+        }
+    }
+
+    /// Add the time since the last tick, unless the reader was away.
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+        if self.focused && now.duration_since(self.last_activity) < IDLE_TIMEOUT {
+            self.elapsed += now.duration_since(self.last_tick);
+            self.session.seconds = self.elapsed.as_secs();
+        }
+        self.last_tick = now;
+    }
+
+    /// The reader jumped elsewhere; nothing in between was read.
+    pub fn jumped(&mut self, line: usize, chapter: usize) {
+        self.furthest = line;
+        self.chapter = chapter;
+    }
+
+    /// The reader moved to `line` in `chapter` by reading on.
+    pub fn moved(&mut self, line: usize, chapter: usize, lines: &Book, counts: &LineCounts) {
+        // going back and reading the same lines again does not count twice
+        if line > self.furthest {
+            self.session.lines += (line - self.furthest) as u64;
+            self.session.read += counts.range(lines, self.furthest..line);
+            self.furthest = line;
+        }
+        if chapter > self.chapter {
+            self.session.chapters_finished += (chapter - self.chapter) as u32;
+            self.chapter = chapter;
+        }
+    }
+
+    pub fn worth_keeping(&self) -> bool {
+        self.session.seconds >= MIN_SESSION_SECS
+    }
+}
+
+/// Line counts summed up front every few lines, so any range of lines adds
+/// up quickly from the nearest totals and a few lines counted on the spot.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct LineCounts {
+    // totals[k] counts lines[..k * COUNT_STRIDE]
+    totals: Vec<Counts>,
+}
+
+impl LineCounts {
+    pub fn new(lines: &Book) -> Self {
+        let mut totals = Vec::with_capacity(lines.len() / COUNT_STRIDE + 1);
+        let mut total = Counts::default();
+        for (i, line) in lines.iter().enumerate() {
+            if i.is_multiple_of(COUNT_STRIDE) {
+                totals.push(total);
+            }
+            total += text::count(line);
+        }
+        if lines.len().is_multiple_of(COUNT_STRIDE) {
+            totals.push(total);
+        }
+        Self { totals }
+    }
+
+    pub fn range(&self, lines: &Book, range: Range<usize>) -> Counts {
+        let end = range.end.min(lines.len());
+        let start = range.start.min(end);
+        self.before(lines, end) - self.before(lines, start)
+    }
+
+    // counts of lines[..i]
+    fn before(&self, lines: &Book, i: usize) -> Counts {
+        let Some(last) = self.totals.len().checked_sub(1) else {
+            return Counts::default();
+        };
+        let k = (i / COUNT_STRIDE).min(last);
+        lines
+            .range(k * COUNT_STRIDE..i)
+            .fold(self.totals[k], |sum, line| sum + text::count(line))
+    }
+}
+
+/// Reading speed in CJK characters plus words per second, once there is enough reading to tell.
+pub fn speed<'a>(sessions: impl IntoIterator<Item = &'a Session>) -> Option<f64> {
+    let total = Summary::of(sessions);
+    (total.seconds >= MIN_SPEED_SECS && total.read.units() > 0)
+        .then(|| total.read.units() as f64 / total.seconds as f64)
+}
+
+/// How long `counts` takes to read at `speed`.
+pub fn time_to_read(counts: Counts, speed: f64) -> Duration {
+    Duration::from_secs_f64(counts.units() as f64 / speed)
+}
+
+/// Sessions added together.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Summary {
+    pub sessions: usize,
+    pub seconds: u64,
+    pub lines: u64,
+    pub read: Counts,
+    pub chapters_finished: u32,
+}
+
+impl Summary {
+    pub fn of<'a>(sessions: impl IntoIterator<Item = &'a Session>) -> Self {
+        let mut sum = Self::default();
+        for s in sessions {
+            sum.sessions += 1;
+            sum.seconds += s.seconds;
+            sum.lines += s.lines;
+            sum.read += s.read;
+            sum.chapters_finished += s.chapters_finished;
+        }
+        sum
+    }
+
+    /// e.g. `1h 05m · 12,345 chars · 3 chapters · 420 chars/min`
+    pub fn describe(&self) -> String {
+        let mut parts = vec![
+            format_span(self.seconds),
+            format!("{} chars", thousands(self.read.chars)),
+            format!("{} chapters", self.chapters_finished),
+        ];
+        if self.seconds > 0 && self.read.units() > 0 {
+            let per_min = self.read.units() as f64 * 60.0 / self.seconds as f64;
+            parts.push(format!("{:.0} {}/min", per_min, unit_name(self.read)));
+        }
+        parts.join(" · ")
+    }
+}
+
+/// What speed is measured in for text like `read`.
+pub fn unit_name(read: Counts) -> &'static str {
+    if read.cjk >= read.words {
+        "chars"
+    } else {
+        "words"
+    }
+}
+
+/// e.g. `2h 05m`, `14m`, `40s`
+pub fn format_span(secs: u64) -> String {
+    if secs >= 3600 {
+        format!("{}h {:02}m", secs / 3600, secs / 60 % 60)
+    } else if secs >= 60 {
+        format!("{}m", secs / 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+fn thousands(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Sessions of the last `days` days (today included), one summary per day.
+pub fn daily(sessions: &[&Session], days: u64, now: u64) -> Vec<(u64, Summary)> {
+    let today = now / DAY;
+    (today + 1 - days.min(today + 1)..=today)
+        .map(|day| {
+            let of_day = sessions.iter().copied().filter(|s| s.start / DAY == day);
+            (day * DAY, Summary::of(of_day))
+        })
+        .collect()
+}
+
+/// Sessions of the last `weeks` weeks (Monday to Sunday), one summary per week.
+pub fn weekly(sessions: &[&Session], weeks: u64, now: u64) -> Vec<(u64, Summary)> {
+    // 1970-01-01 was a Thursday, so weeks start 3 days before it
+    let week_of = |secs: u64| (secs / DAY + 3) / 7;
+    let this_week = week_of(now);
+    (this_week + 1 - weeks.min(this_week + 1)..=this_week)
+        .map(|week| {
+            let of_week = sessions
+                .iter()
+                .copied()
+                .filter(|s| week_of(s.start) == week);
+            ((week * 7).saturating_sub(3) * DAY, Summary::of(of_week))
+        })
+        .collect()
+}
+
+fn print_periods(heading: &str, periods: &[(u64, Summary)]) {
+    println!("{}", heading);
+    for (start, sum) in periods {
+        let date = &state::format_time(*start)[..10];
+        if sum.sessions == 0 {
+            println!("  {}  -", date);
+        } else {
+            println!("  {}  {}", date, sum.describe());
+        }
+    }
+}
+
+/// `noveltui stats`: reading time per day and week, for one book or all of them.
+pub fn print(book: Option<&Path>) -> Result<()> {
+    let books: Vec<BookState> = match book {
+        Some(path) => {
+            let mut state = BookState::load(path);
+            state.path.get_or_insert_with(|| path.to_path_buf());
+            vec![state]
+        }
+        None => state::all_books(),
+    };
+    let now = state::now_secs();
+    let sessions: Vec<&Session> = books.iter().flat_map(|b| &b.sessions).collect();
+    if sessions.is_empty() {
+        println!("No reading recorded yet.");
+        return Ok(());
+    }
+    for b in &books {
+        if b.sessions.is_empty() {
+            continue;
+        }
+        let title = b
+            .path
+            .as_deref()
+            .map_or_else(|| "(unknown book)".to_string(), notes::book_title);
+        println!("{}: {}", title, Summary::of(&b.sessions).describe());
+    }
+    println!();
+    print_periods("Last 7 days (UTC)", &daily(&sessions, 7, now));
+    println!();
+    print_periods(
+        "Last 4 weeks, from Monday (UTC)",
+        &weekly(&sessions, 4, now),
+    );
+    println!();
+    println!("Total: {}", Summary::of(sessions).describe());
+    Ok(())
+}
@@ -0,0 +1,164 @@
+use std::borrow::Cow;
+use std::ops::Range;
+
+use ratatui::{
+    style::Style,
+    text::{Line, Span, Text},
+};
+use serde::{Deserialize, Serialize};
+
+/// Wrap `line` to `width` columns, styling the byte ranges in `styled`.
+/// Ranges must fall on char boundaries; where they overlap, later ones are
+/// patched over earlier ones.
+pub fn wrap_styled(line: &str, width: usize, styled: &[(Range<usize>, Style)]) -> Text<'static> {
+    let wrapped = textwrap::wrap(line, width);
+    if wrapped.is_empty() {
+        return Text::from("");
+    }
+    let base = line.as_ptr() as usize;
+    let rows: Vec<Line> = wrapped
+        .iter()
+        .map(|row| match row {
+            // borrowed rows are slices of `line`, so their byte offset is known
+            Cow::Borrowed(slice) if !styled.is_empty() => {
+                let start = slice.as_ptr() as usize - base;
+                style_row(slice, start, styled)
+            }
+            _ => Line::from(row.to_string()),
+        })
+        .collect();
+    Text::from(rows)
+}
+
+// cut one wrapped row at every range edge inside it and style each piece
+fn style_row(row: &str, row_start: usize, styled: &[(Range<usize>, Style)]) -> Line<'static> {
+    let row_end = row_start + row.len();
+    let mut cuts = vec![row_start, row_end];
+    for (range, _) in styled {
+        for edge in [range.start, range.end] {
+            if edge > row_start && edge < row_end && row.is_char_boundary(edge - row_start) {
+                cuts.push(edge);
+            }
+        }
+    }
+    cuts.sort_unstable();
+    cuts.dedup();
+
+    let spans: Vec<Span> = cuts
+        .windows(2)
+        .map(|w| {
+            let style = styled
+                .iter()
+                .filter(|(r, _)| r.start <= w[0] && r.end >= w[1])
+                .fold(Style::default(), |acc, (_, s)| acc.patch(*s));
+            Span::styled(row[w[0] - row_start..w[1] - row_start].to_string(), style)
+        })
+        .collect();
+    Line::from(spans)
+}
+
+/// Byte offset of char number `char_idx` in `s`, or `s.len()` past the end.
+pub fn char_to_byte(s: &str, char_idx: usize) -> usize {
+    s.char_indices().nth(char_idx).map_or(s.len(), |(b, _)| b)
+}
+
+/// Number of chars before byte offset `byte` in `s`.
+pub fn byte_to_char(s: &str, byte: usize) -> usize {
+    s[..byte.min(s.len())].chars().count()
+}
+
+/// Whether `c` is a Chinese, Japanese or Korean character.
+pub fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30ff}'     // hiragana, katakana
+        | '\u{3400}'..='\u{4dbf}'   // CJK extension A
+        | '\u{4e00}'..='\u{9fff}'   // CJK unified ideographs
+        | '\u{ac00}'..='\u{d7af}'   // hangul syllables
+        | '\u{f900}'..='\u{faff}'   // CJK compatibility ideographs
+        | '\u{20000}'..='\u{2ffff}' // CJK extensions B and later
+    )
+}
+
+/// Amount of text, with CJK characters and Latin words kept apart since one
+/// CJK character reads in about the time of one word.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Counts {
+    /// non-whitespace characters
+    pub chars: u64,
+    pub cjk: u64,
+    /// runs of letters and digits outside CJK scripts
+    pub words: u64,
+}
+
+impl Counts {
+    /// CJK characters plus words, the unit reading speed is measured in.
+    pub fn units(&self) -> u64 {
+        self.cjk + self.words
+    }
+
+    /// Short size for lists, e.g. `3.1k`: CJK characters for mostly-CJK text,
+    /// words otherwise.
+    pub fn compact(&self) -> String {
+        let n = self.cjk.max(self.words);
+        match n {
+            0..1_000 => n.to_string(),
+            1_000..10_000 => format!("{:.1}k", n as f64 / 1e3),
+            10_000..1_000_000 => format!("{}k", n / 1_000),
+            _ => format!("{:.1}M", n as f64 / 1e6),
+        }
+    }
+}
+
+impl std::ops::Add for Counts {
+    type Output = Counts;
+
+    fn add(self, other: Counts) -> Counts {
+        Counts {
+            chars: self.chars + other.chars,
+            cjk: self.cjk + other.cjk,
+            words: self.words + other.words,
+        }
+    }
+}
+
+impl std::ops::AddAssign for Counts {
+    fn add_assign(&mut self, other: Counts) {
+        *self = *self + other;
+    }
+}
+
+impl std::ops::Sub for Counts {
+    type Output = Counts;
+
+    fn sub(self, other: Counts) -> Counts {
+        Counts {
+            chars: self.chars.saturating_sub(other.chars),
+            cjk: self.cjk.saturating_sub(other.cjk),
+            words: self.words.saturating_sub(other.words),
+        }
+    }
+}
+
+/// Count the characters, CJK characters and Latin words in `s`.
+pub fn count(s: &str) -> Counts {
+    let mut counts = Counts::default();
+    let mut in_word = false;
+    for c in s.chars() {
+        if !c.is_whitespace() {
+            counts.chars += 1;
+        }
+        if is_cjk(c) {
+            counts.cjk += 1;
+            in_word = false;
+        } else if c.is_alphanumeric() {
+            if !in_word {
+                counts.words += 1;
+            }
+            in_word = true;
+        } else if !(in_word && (c == '\'' || c == '’')) {
+            // apostrophes stay inside words like "don't"
+            in_word = false;
+        }
+    }
+    counts
+}
@@ -0,0 +1,443 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::str::FromStr;
+
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use ratatui::style::{Color, Modifier, Style};
+use serde::Deserialize;
+
+/// How many colors the terminal can show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum ColorDepth {
+    #[serde(rename = "truecolor", alias = "24bit")]
+    TrueColor,
+    #[serde(rename = "256")]
+    Ansi256,
+    #[serde(rename = "16")]
+    Ansi16,
+}
+
+impl ColorDepth {
+    /// Guess the color depth from `COLORTERM` and `TERM`.
+    pub fn detect() -> Self {
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            return ColorDepth::TrueColor;
+        }
+        let term = env::var("TERM").unwrap_or_default();
+        // terminfo names 24-bit entries `*-direct`, e.g. `xterm-direct`
+        if term.ends_with("-direct") {
+            ColorDepth::TrueColor
+        } else if term.contains("256color") {
+            ColorDepth::Ansi256
+        } else {
+            ColorDepth::Ansi16
+        }
+    }
+}
+
+/// Every style used when drawing the reader.
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub name: String,
+    // whole-screen background and default text
+    pub base: Style,
+    // pane borders
+    pub border: Style,
+    // title bar
+    pub title: Style,
+    // selected chapter in the TOC when focused
+    pub toc_highlight: Style,
+    // selected line in the content when focused
+    pub content_highlight: Style,
+    // selected entry in the bookmark menu when focused
+    pub bookmark_highlight: Style,
+    // selection in any pane that does not have focus
+    pub inactive_highlight: Style,
+    // sentence being read aloud
+    pub speech_highlight: Style,
+    // word picked for dictionary lookup
+    pub lookup_highlight: Style,
+    // footer: focus label and chapter title
+    pub footer_info: Style,
+    // footer: progress and key hints
+    pub footer_hint: Style,
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            name: "dark".to_string(),
+            base: Style::default(),
+            border: Style::default(),
+            title: Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+            toc_highlight: Style::default()
+                .fg(Color::Black)
+                .bg(Color::LightGreen)
+                .add_modifier(Modifier::BOLD),
+            content_highlight: Style::default()
+                .fg(Color::Black)
+                .bg(Color::White)
+                .add_modifier(Modifier::BOLD),
+            bookmark_highlight: Style::default()
+                .add_modifier(Modifier::BOLD)
+                .bg(Color::DarkGray),
+            inactive_highlight: Style::default().fg(Color::Gray),
+            speech_highlight: Style::default().fg(Color::Black).bg(Color::LightYellow),
+            lookup_highlight: Style::default().fg(Color::Black).bg(Color::LightCyan),
+            footer_info: Style::default().fg(Color::LightCyan),
+            footer_hint: Style::default().fg(Color::White),
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            name: "light".to_string(),
+            base: Style::default()
+                .fg(Color::Rgb(0x20, 0x20, 0x20))
+                .bg(Color::Rgb(0xfa, 0xfa, 0xfa)),
+            border: Style::default().fg(Color::Rgb(0x90, 0x90, 0x90)),
+            title: Style::default()
+                .fg(Color::Rgb(0x1f, 0x4e, 0x9c))
+                .add_modifier(Modifier::BOLD),
+            toc_highlight: Style::default()
+                .fg(Color::Rgb(0xff, 0xff, 0xff))
+                .bg(Color::Rgb(0x2e, 0x7d, 0x32))
+                .add_modifier(Modifier::BOLD),
+            content_highlight: Style::default()
+                .fg(Color::Rgb(0x00, 0x00, 0x00))
+                .bg(Color::Rgb(0xd0, 0xe4, 0xff))
+                .add_modifier(Modifier::BOLD),
+            bookmark_highlight: Style::default()
+                .bg(Color::Rgb(0xe0, 0xe0, 0xe0))
+                .add_modifier(Modifier::BOLD),
+            inactive_highlight: Style::default().fg(Color::Rgb(0x70, 0x70, 0x70)),
+            speech_highlight: Style::default()
+                .fg(Color::Rgb(0x00, 0x00, 0x00))
+                .bg(Color::Rgb(0xff, 0xf1, 0x76)),
+            lookup_highlight: Style::default()
+                .fg(Color::Rgb(0x00, 0x00, 0x00))
+                .bg(Color::Rgb(0xa5, 0xd6, 0xff)),
+            footer_info: Style::default().fg(Color::Rgb(0x00, 0x69, 0x7c)),
+            footer_hint: Style::default().fg(Color::Rgb(0x40, 0x40, 0x40)),
+        }
+    }
+
+    pub fn sepia() -> Self {
+        Self {
+            name: "sepia".to_string(),
+            base: Style::default()
+                .fg(Color::Rgb(0x5b, 0x46, 0x36))
+                .bg(Color::Rgb(0xf4, 0xec, 0xd8)),
+            border: Style::default().fg(Color::Rgb(0xa8, 0x92, 0x74)),
+            title: Style::default()
+                .fg(Color::Rgb(0x8b, 0x45, 0x13))
+                .add_modifier(Modifier::BOLD),
+            toc_highlight: Style::default()
+                .fg(Color::Rgb(0xf4, 0xec, 0xd8))
+                .bg(Color::Rgb(0x8b, 0x5a, 0x2b))
+                .add_modifier(Modifier::BOLD),
+            content_highlight: Style::default()
+                .fg(Color::Rgb(0x3e, 0x2c, 0x1c))
+                .bg(Color::Rgb(0xe6, 0xd3, 0xa8))
+                .add_modifier(Modifier::BOLD),
+            bookmark_highlight: Style::default()
+                .bg(Color::Rgb(0xe6, 0xd3, 0xa8))
+                .add_modifier(Modifier::BOLD),
+            inactive_highlight: Style::default().fg(Color::Rgb(0x8c, 0x7a, 0x66)),
+            speech_highlight: Style::default()
+                .fg(Color::Rgb(0x3e, 0x2c, 0x1c))
+                .bg(Color::Rgb(0xf0, 0xc8, 0x7a)),
+            lookup_highlight: Style::default()
+                .fg(Color::Rgb(0xf4, 0xec, 0xd8))
+                .bg(Color::Rgb(0x6b, 0x8e, 0x23)),
+            footer_info: Style::default().fg(Color::Rgb(0x70, 0x4c, 0x2a)),
+            footer_hint: Style::default().fg(Color::Rgb(0x5b, 0x46, 0x36)),
+        }
+    }
+
+    pub fn high_contrast() -> Self {
+        Self {
+            name: "high-contrast".to_string(),
+            base: Style::default().fg(Color::White).bg(Color::Black),
+            border: Style::default().fg(Color::White),
+            title: Style::default()
+                .fg(Color::LightYellow)
+                .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+            toc_highlight: Style::default()
+                .fg(Color::Black)
+                .bg(Color::LightYellow)
+                .add_modifier(Modifier::BOLD),
+            content_highlight: Style::default()
+                .fg(Color::Black)
+                .bg(Color::White)
+                .add_modifier(Modifier::BOLD),
+            bookmark_highlight: Style::default()
+                .fg(Color::Black)
+                .bg(Color::LightCyan)
+                .add_modifier(Modifier::BOLD),
+            inactive_highlight: Style::default()
+                .fg(Color::White)
+                .add_modifier(Modifier::UNDERLINED),
+            speech_highlight: Style::default()
+                .fg(Color::Black)
+                .bg(Color::LightYellow)
+                .add_modifier(Modifier::BOLD),
+            lookup_highlight: Style::default()
+                .fg(Color::Black)
+                .bg(Color::LightCyan)
+                .add_modifier(Modifier::BOLD),
+            footer_info: Style::default()
+                .fg(Color::LightCyan)
+                .add_modifier(Modifier::BOLD),
+            footer_hint: Style::default().fg(Color::White),
+        }
+    }
+
+    pub fn builtin() -> Vec<Self> {
+        vec![
+            Self::dark(),
+            Self::light(),
+            Self::sepia(),
+            Self::high_contrast(),
+        ]
+    }
+
+    /// Downgrade every color in the theme to what `depth` can show.
+    pub fn adapt(mut self, depth: ColorDepth) -> Self {
+        for style in [
+            &mut self.base,
+            &mut self.border,
+            &mut self.title,
+            &mut self.toc_highlight,
+            &mut self.content_highlight,
+            &mut self.bookmark_highlight,
+            &mut self.inactive_highlight,
+            &mut self.speech_highlight,
+            &mut self.lookup_highlight,
+            &mut self.footer_info,
+            &mut self.footer_hint,
+        ] {
+            style.fg = style.fg.map(|c| downgrade(c, depth));
+            style.bg = style.bg.map(|c| downgrade(c, depth));
+        }
+        self
+    }
+}
+
+/// A style as written in the config file. Unset fields keep the inherited value.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StyleConfig {
+    pub fg: Option<String>,
+    pub bg: Option<String>,
+    pub bold: Option<bool>,
+    pub italic: Option<bool>,
+    pub underlined: Option<bool>,
+    pub reversed: Option<bool>,
+}
+
+impl StyleConfig {
+    fn apply(&self, mut style: Style) -> Result<Style> {
+        if let Some(fg) = &self.fg {
+            style.fg = Some(parse_color(fg)?);
+        }
+        if let Some(bg) = &self.bg {
+            style.bg = Some(parse_color(bg)?);
+        }
+        for (flag, modifier) in [
+            (self.bold, Modifier::BOLD),
+            (self.italic, Modifier::ITALIC),
+            (self.underlined, Modifier::UNDERLINED),
+            (self.reversed, Modifier::REVERSED),
+        ] {
+            match flag {
+                Some(true) => style = style.add_modifier(modifier),
+                Some(false) => style = style.remove_modifier(modifier),
+                None => {}
+            }
+        }
+        Ok(style)
+    }
+}
+
+/// A user theme from the config file.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeConfig {
+    /// theme to take unset styles from (defaults to `dark`)
+    pub inherits: Option<String>,
+    pub base: Option<StyleConfig>,
+    pub border: Option<StyleConfig>,
+    pub title: Option<StyleConfig>,
+    pub toc_highlight: Option<StyleConfig>,
+    pub content_highlight: Option<StyleConfig>,
+    pub bookmark_highlight: Option<StyleConfig>,
+    pub inactive_highlight: Option<StyleConfig>,
+    pub speech_highlight: Option<StyleConfig>,
+    pub lookup_highlight: Option<StyleConfig>,
+    pub footer_info: Option<StyleConfig>,
+    pub footer_hint: Option<StyleConfig>,
+}
+
+/// Built-in themes followed by the user's themes, adapted to `depth`.
+/// A user theme with a built-in name replaces the built-in one.
+pub fn load_themes(user: &BTreeMap<String, ThemeConfig>, depth: ColorDepth) -> Result<Vec<Theme>> {
+    let mut themes = Theme::builtin();
+    for name in user.keys() {
+        let theme = resolve(name, user, &mut Vec::new())?;
+        match themes.iter_mut().find(|t| t.name == *name) {
+            Some(existing) => *existing = theme,
+            None => themes.push(theme),
+        }
+    }
+    Ok(themes.into_iter().map(|t| t.adapt(depth)).collect())
+}
+
+fn resolve(
+    name: &str,
+    user: &BTreeMap<String, ThemeConfig>,
+    seen: &mut Vec<String>,
+) -> Result<Theme> {
+    let Some(cfg) = user.get(name) else {
+        return Theme::builtin()
+            .into_iter()
+            .find(|t| t.name == name)
+            .ok_or_else(|| eyre!("Unknown theme \"{}\"", name));
+    };
+    if seen.iter().any(|s| s == name) {
+        return Err(eyre!("Theme \"{}\" inherits from itself", name));
+    }
+    seen.push(name.to_string());
+    let parent = cfg.inherits.as_deref().unwrap_or("dark");
+    // a user theme may override a built-in one of the same name and still inherit from it
+    let mut theme = if parent == name {
+        Theme::builtin()
+            .into_iter()
+            .find(|t| t.name == name)
+            .ok_or_else(|| eyre!("Theme \"{}\" inherits from itself", name))?
+    } else {
+        resolve(parent, user, seen)?
+    };
+    theme.name = name.to_string();
+    for (style, patch) in [
+        (&mut theme.base, &cfg.base),
+        (&mut theme.border, &cfg.border),
+        (&mut theme.title, &cfg.title),
+        (&mut theme.toc_highlight, &cfg.toc_highlight),
+        (&mut theme.content_highlight, &cfg.content_highlight),
+        (&mut theme.bookmark_highlight, &cfg.bookmark_highlight),
+        (&mut theme.inactive_highlight, &cfg.inactive_highlight),
+        (&mut theme.speech_highlight, &cfg.speech_highlight),
+        (&mut theme.lookup_highlight, &cfg.lookup_highlight),
+        (&mut theme.footer_info, &cfg.footer_info),
+        (&mut theme.footer_hint, &cfg.footer_hint),
+    ] {
+        if let Some(patch) = patch {
+            *style = patch
+                .apply(*style)
+                .map_err(|e| eyre!("Theme \"{}\": {}", name, e))?;
+        }
+    }
+    Ok(theme)
+}
+
+/// Parse `#rrggbb`, a color name like `lightgreen`, or a 256-color index.
+pub fn parse_color(s: &str) -> Result<Color> {
+    Color::from_str(s.trim()).map_err(|_| eyre!("Invalid color \"{}\"", s))
+}
+
+// xterm's default values for the 16 basic colors
+const ANSI16: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0x00, 0x00, 0x00)),
+    (Color::Red, (0xcd, 0x00, 0x00)),
+    (Color::Green, (0x00, 0xcd, 0x00)),
+    (Color::Yellow, (0xcd, 0xcd, 0x00)),
+    (Color::Blue, (0x00, 0x00, 0xee)),
+    (Color::Magenta, (0xcd, 0x00, 0xcd)),
+    (Color::Cyan, (0x00, 0xcd, 0xcd)),
+    (Color::Gray, (0xe5, 0xe5, 0xe5)),
+    (Color::DarkGray, (0x7f, 0x7f, 0x7f)),
+    (Color::LightRed, (0xff, 0x00, 0x00)),
+    (Color::LightGreen, (0x00, 0xff, 0x00)),
+    (Color::LightYellow, (0xff, 0xff, 0x00)),
+    (Color::LightBlue, (0x5c, 0x5c, 0xff)),
+    (Color::LightMagenta, (0xff, 0x00, 0xff)),
+    (Color::LightCyan, (0x00, 0xff, 0xff)),
+    (Color::White, (0xff, 0xff, 0xff)),
+];
+
+const CUBE_LEVELS: [u8; 6] = [0x00, 0x5f, 0x87, 0xaf, 0xd7, 0xff];
+
+fn downgrade(color: Color, depth: ColorDepth) -> Color {
+    match (depth, color) {
+        (ColorDepth::TrueColor, c) => c,
+        (ColorDepth::Ansi256, Color::Rgb(r, g, b)) => Color::Indexed(rgb_to_256(r, g, b)),
+        (ColorDepth::Ansi16, Color::Rgb(r, g, b)) => rgb_to_16(r, g, b),
+        (ColorDepth::Ansi16, Color::Indexed(i)) => {
+            let (r, g, b) = indexed_to_rgb(i);
+            rgb_to_16(r, g, b)
+        }
+        (_, c) => c,
+    }
+}
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let d = |a: u8, b: u8| (a as i32 - b as i32).unsigned_abs();
+    d(r1, r2).pow(2) + d(g1, g2).pow(2) + d(b1, b2).pow(2)
+}
+
+fn rgb_to_256(r: u8, g: u8, b: u8) -> u8 {
+    let level = |v: u8| -> usize {
+        CUBE_LEVELS
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, l)| (v as i32 - **l as i32).abs())
+            .map(|(i, _)| i)
+            .unwrap_or(0)
+    };
+    let (ri, gi, bi) = (level(r), level(g), level(b));
+    let cube = (CUBE_LEVELS[ri], CUBE_LEVELS[gi], CUBE_LEVELS[bi]);
+    let cube_index = 16 + 36 * ri + 6 * gi + bi;
+
+    // the 24-step grayscale ramp is often closer for desaturated colors
+    let avg = (r as u32 + g as u32 + b as u32) / 3;
+    let gray_step = (avg.saturating_sub(8) / 10).min(23) as u8;
+    let gray_value = 8 + gray_step * 10;
+    let gray = (gray_value, gray_value, gray_value);
+
+    if distance((r, g, b), gray) < distance((r, g, b), cube) {
+        232 + gray_step
+    } else {
+        cube_index as u8
+    }
+}
+
+fn rgb_to_16(r: u8, g: u8, b: u8) -> Color {
+    ANSI16
+        .iter()
+        .min_by_key(|(_, rgb)| distance((r, g, b), *rgb))
+        .map(|(c, _)| *c)
+        .unwrap_or(Color::Reset)
+}
+
+fn indexed_to_rgb(i: u8) -> (u8, u8, u8) {
+    match i {
+        0..=15 => ANSI16[i as usize].1,
+        16..=231 => {
+            let i = i - 16;
+            (
+                CUBE_LEVELS[(i / 36) as usize],
+                CUBE_LEVELS[((i / 6) % 6) as usize],
+                CUBE_LEVELS[(i % 6) as usize],
+            )
+        }
+        _ => {
+            let v = 8 + (i - 232) * 10;
+            (v, v, v)
+        }
+    }
+}
@@ -0,0 +1,291 @@
+use std::io::{self, Write};
+use std::ops::Range;
+use std::process::{Child, Command, Stdio};
+
+use serde::Deserialize;
+
+use crate::state::ReadingPosition;
+
+/// `[tts]` section of the config: any command that reads text on stdin.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TtsConfig {
+    pub command: String,
+    /// arguments; `{rate}` is replaced with the current rate
+    pub args: Vec<String>,
+    /// starting rate, in the engine's own unit (words per minute for espeak-ng)
+    pub rate: u32,
+}
+
+impl Default for TtsConfig {
+    fn default() -> Self {
+        Self {
+            command: "espeak-ng".to_string(),
+            args: vec!["-s".to_string(), "{rate}".to_string()],
+            rate: 175,
+        }
+    }
+}
+
+/// A read-aloud session: the sentence being spoken and the engine speaking it.
+#[derive(Debug)]
+pub struct Tts {
+    config: TtsConfig,
+    pub rate: u32,
+    pub paused: bool,
+    /// line the current sentence is on
+    pub at: ReadingPosition,
+    /// byte range of the current sentence in that line
+    pub sentence: Range<usize>,
+    child: Option<Child>,
+}
+
+impl Tts {
+    pub fn new(config: TtsConfig, at: ReadingPosition, sentence: Range<usize>) -> Self {
+        Self {
+            rate: config.rate.max(1),
+            config,
+            paused: false,
+            at,
+            sentence,
+            child: None,
+        }
+    }
+
+    /// Start speaking `text`, cutting off whatever was being said.
+    pub fn speak(&mut self, text: &str) -> io::Result<()> {
+        self.stop();
+        let rate = self.rate.to_string();
+        let mut child = Command::new(&self.config.command)
+            .args(self.config.args.iter().map(|a| a.replace("{rate}", &rate)))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // dropping stdin closes it, which tells the engine the text is complete
+            stdin.write_all(text.as_bytes())?;
+        }
+        self.child = Some(child);
+        Ok(())
+    }
+
+    /// Whether the engine is still busy with the last sentence.
+    pub fn is_speaking(&mut self) -> bool {
+        match self.child.as_mut().map(|c| c.try_wait()) {
+            Some(Ok(None)) => true,
+            Some(_) => {
+                self.child = None;
+                false
+            }
+            None => false,
+        }
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+
+    pub fn faster(&mut self) {
+        self.rate += (self.rate / 10).max(1);
+    }
+
+    pub fn slower(&mut self) {
+        self.rate = self.rate.saturating_sub((self.rate / 10).max(1)).max(1);
+    }
+
+    /// Short footer label, e.g. `🔊 175`.
+    pub fn label(&self) -> String {
+        let state = if self.paused { "⏸" } else { "🔊" };
+        format!("{} {}", state, self.rate)
+    }
+}
+
+impl Drop for Tts {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+const TERMINATORS: &[char] = &['。', '！', '？', '…', '；', '!', '?', ';'];
+const CLOSERS: &[char] = &['”', '’', '」', '』', '）', '"', '\'', ')'];
+
+/// The sentence in `line` starting at or after byte `from`, without surrounding spaces.
+pub fn next_sentence(line: &str, from: usize) -> Option<Range<usize>> {
+    let rest = line.get(from..)?;
+    let start = from + (rest.len() - rest.trim_start().len());
+    if start >= line.len() {
+        return None;
+    }
+    let mut chars = line[start..].char_indices().peekable();
+    let mut end = line.len();
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|(_, n)| *n);
+        // a period only ends a sentence before a space, so "3.5" and "Mr.Smith" stay whole
+        let ends = TERMINATORS.contains(&c)
+            || (c == '.'
+                && next.is_none_or(char::is_whitespace)
+                && !is_abbreviation(&line[start..start + i]))
+            || c == '．';
+        if ends {
+            end = start + i + c.len_utf8();
+            // swallow repeated marks and closing quotes: "！？」"
+            while let Some((j, n)) = chars.peek().copied() {
+                if TERMINATORS.contains(&n) || CLOSERS.contains(&n) || n == '.' {
+                    end = start + j + n.len_utf8();
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            break;
+        }
+    }
+    let trimmed = line[start..end].trim_end();
+    Some(start..start + trimmed.len())
+}
+
+const ABBREVIATIONS: &[&str] = &["Mr", "Mrs", "Ms", "Dr", "St", "Jr", "Sr", "vs", "etc"];
+
+// whether the text before a period ends with a title like "Mr"
+fn is_abbreviation(before: &str) -> bool {
+    let word = before
+        .rsplit(|c: char| !c.is_alphabetic())
+        .next()
+        .unwrap_or("");
+    ABBREVIATIONS.contains(&word)
+}
+
+/// Every sentence in `line`, in order.
+pub fn sentences(line: &str) -> Vec<Range<usize>> {
+    let mut out = Vec::new();
+    let mut from = 0;
+    while let Some(range) = next_sentence(line, from) {
+        from = range.end;
+        out.push(range);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    fn split(line: &str) -> Vec<&str> {
+        sentences(line).into_iter().map(|r| &line[r]).collect()
+    }
+
+    #[test]
+    fn splits_cjk_sentences() {
+        assert_eq!(
+            split("他走了。她笑了！真的吗？好；"),
+            ["他走了。", "她笑了！", "真的吗？", "好；"]
+        );
+        assert_eq!(split("等等……然后呢"), ["等等……", "然后呢"]);
+    }
+
+    #[test]
+    fn splits_latin_sentences() {
+        assert_eq!(
+            split("It rained. We left! Why?  Done"),
+            ["It rained.", "We left!", "Why?", "Done"]
+        );
+        // no space after the period, or a title before it
+        assert_eq!(split("It cost 3.5 coins."), ["It cost 3.5 coins."]);
+        assert_eq!(
+            split("Mr. Smith came. He sat."),
+            ["Mr. Smith came.", "He sat."]
+        );
+    }
+
+    #[test]
+    fn keeps_closing_quotes_with_the_sentence() {
+        assert_eq!(split("“走吧！”他说。"), ["“走吧！”", "他说。"]);
+        assert_eq!(split("「何？！」と言った"), ["「何？！」", "と言った"]);
+        assert_eq!(
+            split("\"Stop!\" she said. (Quietly!) Then"),
+            ["\"Stop!\"", "she said.", "(Quietly!)", "Then"]
+        );
+    }
+
+    #[test]
+    fn empty_lines_have_no_sentences() {
+        assert!(split("").is_empty());
+        assert!(split("   \u{3000}").is_empty());
+        assert_eq!(next_sentence("一。", 6), None);
+        assert_eq!(next_sentence("一。", 10), None);
+    }
+
+    #[test]
+    fn next_sentence_skips_leading_space() {
+        let line = "One.   Two.";
+        assert_eq!(next_sentence(line, 4), Some(7..11));
+    }
+
+    fn tts(command: &str, args: &[&str]) -> Tts {
+        let config = TtsConfig {
+            command: command.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            rate: 175,
+        };
+        Tts::new(config, ReadingPosition::default(), 0..0)
+    }
+
+    fn wait_until_quiet(tts: &mut Tts) {
+        let start = Instant::now();
+        while tts.is_speaking() {
+            assert!(start.elapsed() < Duration::from_secs(5), "still speaking");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn speaks_with_the_current_rate() {
+        let out = std::env::temp_dir().join(format!("noveltui-tts-{}", std::process::id()));
+        let out_arg = out.to_string_lossy();
+        // a stand-in engine that writes down its rate and the text it got
+        let mut tts = tts(
+            "sh",
+            &[
+                "-c",
+                "echo \"$0\" > \"$1\"; cat >> \"$1\"",
+                "{rate}",
+                &out_arg,
+            ],
+        );
+        tts.faster();
+        tts.speak("你好。").unwrap();
+        wait_until_quiet(&mut tts);
+        let written = fs::read_to_string(&out).unwrap();
+        let _ = fs::remove_file(&out);
+        assert_eq!(written, format!("{}\n你好。", tts.rate));
+        assert_eq!(tts.rate, 192);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stopping_ends_the_engine() {
+        let mut tts = tts("sleep", &["10"]);
+        tts.speak("").unwrap();
+        assert!(tts.is_speaking());
+        let start = Instant::now();
+        tts.stop();
+        assert!(!tts.is_speaking());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn missing_engine_is_an_error() {
+        let mut tts = tts("noveltui-no-such-engine", &[]);
+        assert!(tts.speak("text").is_err());
+        assert!(!tts.is_speaking());
+    }
+}